@@ -0,0 +1,87 @@
+use std::arch::x86_64::*;
+
+use solana_sdk::keccak::Hash as KeccakHash;
+
+use super::{
+    keccak::{hash_lanes, Lanes},
+    HashBackend, Midstate,
+};
+
+/// Hashes four nonces per call, one per 64-bit lane of a 256-bit register.
+pub struct Avx2Backend(());
+
+impl Avx2Backend {
+    pub fn new() -> Option<Self> {
+        is_x86_feature_detected!("avx2").then_some(Self(()))
+    }
+}
+
+impl HashBackend for Avx2Backend {
+    fn name(&self) -> &'static str {
+        "avx2"
+    }
+
+    fn lanes(&self) -> usize {
+        <Avx2Lanes as Lanes>::WIDTH
+    }
+
+    fn hash_batch(&self, midstate: &Midstate, nonce: u64, out: &mut [KeccakHash]) {
+        // Safety: the backend can only be constructed when the CPU supports AVX2.
+        unsafe { hash_batch_avx2(midstate, nonce, out) }
+    }
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn hash_batch_avx2(midstate: &Midstate, nonce: u64, out: &mut [KeccakHash]) {
+    hash_lanes::<Avx2Lanes>(midstate, nonce, out)
+}
+
+/// Only ever used inside `hash_batch_avx2`, so every intrinsic is inlined
+/// into AVX2 code.
+#[derive(Clone, Copy)]
+struct Avx2Lanes(__m256i);
+
+impl Lanes for Avx2Lanes {
+    const WIDTH: usize = 4;
+
+    #[inline(always)]
+    fn splat(v: u64) -> Self {
+        unsafe { Self(_mm256_set1_epi64x(v as i64)) }
+    }
+
+    #[inline(always)]
+    fn counter(nonce: u64) -> Self {
+        unsafe {
+            Self(_mm256_set_epi64x(
+                nonce.wrapping_add(3) as i64,
+                nonce.wrapping_add(2) as i64,
+                nonce.wrapping_add(1) as i64,
+                nonce as i64,
+            ))
+        }
+    }
+
+    #[inline(always)]
+    fn xor(self, other: Self) -> Self {
+        unsafe { Self(_mm256_xor_si256(self.0, other.0)) }
+    }
+
+    #[inline(always)]
+    fn andnot(self, other: Self) -> Self {
+        unsafe { Self(_mm256_andnot_si256(self.0, other.0)) }
+    }
+
+    #[inline(always)]
+    fn rol(self, n: u32) -> Self {
+        unsafe {
+            let left = _mm256_sll_epi64(self.0, _mm_cvtsi32_si128(n as i32));
+            let right = _mm256_srl_epi64(self.0, _mm_cvtsi32_si128(64 - n as i32));
+            Self(_mm256_or_si256(left, right))
+        }
+    }
+
+    #[inline(always)]
+    fn store(self, out: &mut [u64]) {
+        unsafe { _mm256_storeu_si256(out.as_mut_ptr() as *mut __m256i, self.0) }
+    }
+}
@@ -0,0 +1,286 @@
+use std::arch::asm;
+
+use solana_sdk::keccak::Hash as KeccakHash;
+
+use super::{keccak::ROUND_CONSTANTS, HashBackend, Midstate};
+
+/// Hashes eight nonces per call, one per 64-bit lane of a 512-bit register.
+///
+/// AVX-512 intrinsics are not stable on the pinned toolchain, so the whole
+/// permutation is written as inline assembly, with the 25 state lanes kept in
+/// `zmm0`-`zmm24` and `zmm25`-`zmm30` as scratch. Rho and pi rotate lanes in
+/// place along pi's single 24-lane cycle, as `keccak::rho_pi_chi_iota` does.
+pub struct Avx512Backend(());
+
+impl Avx512Backend {
+    pub fn new() -> Option<Self> {
+        is_x86_feature_detected!("avx512f").then_some(Self(()))
+    }
+}
+
+impl HashBackend for Avx512Backend {
+    fn name(&self) -> &'static str {
+        "avx512"
+    }
+
+    fn lanes(&self) -> usize {
+        8
+    }
+
+    fn hash_batch(&self, midstate: &Midstate, nonce: u64, out: &mut [KeccakHash]) {
+        // The midstate lanes followed by the column parities the asm reads.
+        let mut state = [0u64; 28];
+        state[..25].copy_from_slice(&midstate.lanes);
+        state[25] = midstate.c1;
+        state[26] = midstate.c0_rol;
+        state[27] = midstate.c3;
+        let nonces: [u64; 8] = std::array::from_fn(|i| nonce.wrapping_add(i as u64));
+        let mut words = [[0u64; 8]; 4];
+
+        // Safety: the backend can only be constructed when the CPU supports
+        // AVX-512F, every pointer is valid for the reads and writes below, and
+        // every vector register the asm writes is declared clobbered.
+        unsafe {
+            asm!(
+            // First round theta, finished from the midstate as in `hash_lanes`
+            "vmovdqu64 zmm25, [{nonce}]",
+            "vpbroadcastq zmm26, qword ptr [{midstate} + 8 * 27]",
+            "vpxorq zmm26, zmm26, zmm25",
+            "vprolq zmm27, zmm26, 1",
+            "vpxorq zmm27, zmm27, qword ptr [{midstate} + 8 * 25]{{1to8}}",
+            "vpxorq zmm28, zmm26, qword ptr [{midstate} + 8 * 26]{{1to8}}",
+            "vpbroadcastq zmm0, qword ptr [{midstate} + 8 * 0]",
+            "vpbroadcastq zmm1, qword ptr [{midstate} + 8 * 1]",
+            "vpbroadcastq zmm2, qword ptr [{midstate} + 8 * 2]",
+            "vpxorq zmm2, zmm2, zmm27",
+            "vpbroadcastq zmm3, qword ptr [{midstate} + 8 * 3]",
+            "vpbroadcastq zmm4, qword ptr [{midstate} + 8 * 4]",
+            "vpxorq zmm4, zmm4, zmm28",
+            "vpbroadcastq zmm5, qword ptr [{midstate} + 8 * 5]",
+            "vpbroadcastq zmm6, qword ptr [{midstate} + 8 * 6]",
+            "vpbroadcastq zmm7, qword ptr [{midstate} + 8 * 7]",
+            "vpxorq zmm7, zmm7, zmm27",
+            "vpbroadcastq zmm8, qword ptr [{midstate} + 8 * 8]",
+            "vpbroadcastq zmm9, qword ptr [{midstate} + 8 * 9]",
+            "vpxorq zmm9, zmm9, zmm28",
+            "vpbroadcastq zmm10, qword ptr [{midstate} + 8 * 10]",
+            "vpbroadcastq zmm11, qword ptr [{midstate} + 8 * 11]",
+            "vpbroadcastq zmm12, qword ptr [{midstate} + 8 * 12]",
+            "vpxorq zmm12, zmm12, zmm27",
+            "vpbroadcastq zmm13, qword ptr [{midstate} + 8 * 13]",
+            "vpbroadcastq zmm14, qword ptr [{midstate} + 8 * 14]",
+            "vpxorq zmm14, zmm14, zmm28",
+            "vpbroadcastq zmm15, qword ptr [{midstate} + 8 * 15]",
+            "vpbroadcastq zmm16, qword ptr [{midstate} + 8 * 16]",
+            "vpbroadcastq zmm17, qword ptr [{midstate} + 8 * 17]",
+            "vpxorq zmm17, zmm17, zmm27",
+            "vpbroadcastq zmm18, qword ptr [{midstate} + 8 * 18]",
+            "vpbroadcastq zmm19, qword ptr [{midstate} + 8 * 19]",
+            "vpxorq zmm19, zmm19, zmm28",
+            "vpbroadcastq zmm20, qword ptr [{midstate} + 8 * 20]",
+            "vpbroadcastq zmm21, qword ptr [{midstate} + 8 * 21]",
+            "vpbroadcastq zmm22, qword ptr [{midstate} + 8 * 22]",
+            "vpxorq zmm22, zmm22, zmm27",
+            "vpbroadcastq zmm23, qword ptr [{midstate} + 8 * 23]",
+            "vpbroadcastq zmm24, qword ptr [{midstate} + 8 * 24]",
+            "vpxorq zmm24, zmm24, zmm28",
+            "vpxorq zmm8, zmm8, zmm25",
+            "jmp 3f",
+            "2:",
+            // Theta
+            "vmovdqa64 zmm25, zmm0",
+            "vpternlogq zmm25, zmm5, zmm10, 0x96",
+            "vpternlogq zmm25, zmm15, zmm20, 0x96",
+            "vmovdqa64 zmm26, zmm1",
+            "vpternlogq zmm26, zmm6, zmm11, 0x96",
+            "vpternlogq zmm26, zmm16, zmm21, 0x96",
+            "vmovdqa64 zmm27, zmm2",
+            "vpternlogq zmm27, zmm7, zmm12, 0x96",
+            "vpternlogq zmm27, zmm17, zmm22, 0x96",
+            "vmovdqa64 zmm28, zmm3",
+            "vpternlogq zmm28, zmm8, zmm13, 0x96",
+            "vpternlogq zmm28, zmm18, zmm23, 0x96",
+            "vmovdqa64 zmm29, zmm4",
+            "vpternlogq zmm29, zmm9, zmm14, 0x96",
+            "vpternlogq zmm29, zmm19, zmm24, 0x96",
+            "vprolq zmm30, zmm26, 1",
+            "vpxorq zmm30, zmm30, zmm29",
+            "vpxorq zmm0, zmm0, zmm30",
+            "vpxorq zmm5, zmm5, zmm30",
+            "vpxorq zmm10, zmm10, zmm30",
+            "vpxorq zmm15, zmm15, zmm30",
+            "vpxorq zmm20, zmm20, zmm30",
+            "vprolq zmm30, zmm27, 1",
+            "vpxorq zmm30, zmm30, zmm25",
+            "vpxorq zmm1, zmm1, zmm30",
+            "vpxorq zmm6, zmm6, zmm30",
+            "vpxorq zmm11, zmm11, zmm30",
+            "vpxorq zmm16, zmm16, zmm30",
+            "vpxorq zmm21, zmm21, zmm30",
+            "vprolq zmm30, zmm28, 1",
+            "vpxorq zmm30, zmm30, zmm26",
+            "vpxorq zmm2, zmm2, zmm30",
+            "vpxorq zmm7, zmm7, zmm30",
+            "vpxorq zmm12, zmm12, zmm30",
+            "vpxorq zmm17, zmm17, zmm30",
+            "vpxorq zmm22, zmm22, zmm30",
+            "vprolq zmm30, zmm29, 1",
+            "vpxorq zmm30, zmm30, zmm27",
+            "vpxorq zmm3, zmm3, zmm30",
+            "vpxorq zmm8, zmm8, zmm30",
+            "vpxorq zmm13, zmm13, zmm30",
+            "vpxorq zmm18, zmm18, zmm30",
+            "vpxorq zmm23, zmm23, zmm30",
+            "vprolq zmm30, zmm25, 1",
+            "vpxorq zmm30, zmm30, zmm28",
+            "vpxorq zmm4, zmm4, zmm30",
+            "vpxorq zmm9, zmm9, zmm30",
+            "vpxorq zmm14, zmm14, zmm30",
+            "vpxorq zmm19, zmm19, zmm30",
+            "vpxorq zmm24, zmm24, zmm30",
+            "3:",
+            // Rho and pi
+            "vmovdqa64 zmm25, zmm1",
+            "vmovdqa64 zmm26, zmm10",
+            "vprolq zmm10, zmm25, 1",
+            "vmovdqa64 zmm25, zmm7",
+            "vprolq zmm7, zmm26, 3",
+            "vmovdqa64 zmm26, zmm11",
+            "vprolq zmm11, zmm25, 6",
+            "vmovdqa64 zmm25, zmm17",
+            "vprolq zmm17, zmm26, 10",
+            "vmovdqa64 zmm26, zmm18",
+            "vprolq zmm18, zmm25, 15",
+            "vmovdqa64 zmm25, zmm3",
+            "vprolq zmm3, zmm26, 21",
+            "vmovdqa64 zmm26, zmm5",
+            "vprolq zmm5, zmm25, 28",
+            "vmovdqa64 zmm25, zmm16",
+            "vprolq zmm16, zmm26, 36",
+            "vmovdqa64 zmm26, zmm8",
+            "vprolq zmm8, zmm25, 45",
+            "vmovdqa64 zmm25, zmm21",
+            "vprolq zmm21, zmm26, 55",
+            "vmovdqa64 zmm26, zmm24",
+            "vprolq zmm24, zmm25, 2",
+            "vmovdqa64 zmm25, zmm4",
+            "vprolq zmm4, zmm26, 14",
+            "vmovdqa64 zmm26, zmm15",
+            "vprolq zmm15, zmm25, 27",
+            "vmovdqa64 zmm25, zmm23",
+            "vprolq zmm23, zmm26, 41",
+            "vmovdqa64 zmm26, zmm19",
+            "vprolq zmm19, zmm25, 56",
+            "vmovdqa64 zmm25, zmm13",
+            "vprolq zmm13, zmm26, 8",
+            "vmovdqa64 zmm26, zmm12",
+            "vprolq zmm12, zmm25, 25",
+            "vmovdqa64 zmm25, zmm2",
+            "vprolq zmm2, zmm26, 43",
+            "vmovdqa64 zmm26, zmm20",
+            "vprolq zmm20, zmm25, 62",
+            "vmovdqa64 zmm25, zmm14",
+            "vprolq zmm14, zmm26, 18",
+            "vmovdqa64 zmm26, zmm22",
+            "vprolq zmm22, zmm25, 39",
+            "vmovdqa64 zmm25, zmm9",
+            "vprolq zmm9, zmm26, 61",
+            "vmovdqa64 zmm26, zmm6",
+            "vprolq zmm6, zmm25, 20",
+            "vmovdqa64 zmm25, zmm1",
+            "vprolq zmm1, zmm26, 44",
+            // Chi, keeping copies of the first two lanes of each row
+            "vmovdqa64 zmm25, zmm0",
+            "vmovdqa64 zmm26, zmm1",
+            "vpternlogq zmm0, zmm1, zmm2, 0xd2",
+            "vpternlogq zmm1, zmm2, zmm3, 0xd2",
+            "vpternlogq zmm2, zmm3, zmm4, 0xd2",
+            "vpternlogq zmm3, zmm4, zmm25, 0xd2",
+            "vpternlogq zmm4, zmm25, zmm26, 0xd2",
+            "vmovdqa64 zmm25, zmm5",
+            "vmovdqa64 zmm26, zmm6",
+            "vpternlogq zmm5, zmm6, zmm7, 0xd2",
+            "vpternlogq zmm6, zmm7, zmm8, 0xd2",
+            "vpternlogq zmm7, zmm8, zmm9, 0xd2",
+            "vpternlogq zmm8, zmm9, zmm25, 0xd2",
+            "vpternlogq zmm9, zmm25, zmm26, 0xd2",
+            "vmovdqa64 zmm25, zmm10",
+            "vmovdqa64 zmm26, zmm11",
+            "vpternlogq zmm10, zmm11, zmm12, 0xd2",
+            "vpternlogq zmm11, zmm12, zmm13, 0xd2",
+            "vpternlogq zmm12, zmm13, zmm14, 0xd2",
+            "vpternlogq zmm13, zmm14, zmm25, 0xd2",
+            "vpternlogq zmm14, zmm25, zmm26, 0xd2",
+            "vmovdqa64 zmm25, zmm15",
+            "vmovdqa64 zmm26, zmm16",
+            "vpternlogq zmm15, zmm16, zmm17, 0xd2",
+            "vpternlogq zmm16, zmm17, zmm18, 0xd2",
+            "vpternlogq zmm17, zmm18, zmm19, 0xd2",
+            "vpternlogq zmm18, zmm19, zmm25, 0xd2",
+            "vpternlogq zmm19, zmm25, zmm26, 0xd2",
+            "vmovdqa64 zmm25, zmm20",
+            "vmovdqa64 zmm26, zmm21",
+            "vpternlogq zmm20, zmm21, zmm22, 0xd2",
+            "vpternlogq zmm21, zmm22, zmm23, 0xd2",
+            "vpternlogq zmm22, zmm23, zmm24, 0xd2",
+            "vpternlogq zmm23, zmm24, zmm25, 0xd2",
+            "vpternlogq zmm24, zmm25, zmm26, 0xd2",
+            // Iota
+            "vpxorq zmm0, zmm0, qword ptr [{rc}]{{1to8}}",
+            "add {rc}, 8",
+            "dec {rounds}",
+            "jnz 2b",
+            // The hash is the first four lanes
+            "vmovdqu64 [{out} + 64 * 0], zmm0",
+            "vmovdqu64 [{out} + 64 * 1], zmm1",
+            "vmovdqu64 [{out} + 64 * 2], zmm2",
+            "vmovdqu64 [{out} + 64 * 3], zmm3",
+                midstate = in(reg) state.as_ptr(),
+                nonce = in(reg) nonces.as_ptr(),
+                out = in(reg) words.as_mut_ptr(),
+                rc = inout(reg) ROUND_CONSTANTS.as_ptr() => _,
+                rounds = inout(reg) ROUND_CONSTANTS.len() => _,
+                out("zmm0") _,
+                out("zmm1") _,
+                out("zmm2") _,
+                out("zmm3") _,
+                out("zmm4") _,
+                out("zmm5") _,
+                out("zmm6") _,
+                out("zmm7") _,
+                out("zmm8") _,
+                out("zmm9") _,
+                out("zmm10") _,
+                out("zmm11") _,
+                out("zmm12") _,
+                out("zmm13") _,
+                out("zmm14") _,
+                out("zmm15") _,
+                out("zmm16") _,
+                out("zmm17") _,
+                out("zmm18") _,
+                out("zmm19") _,
+                out("zmm20") _,
+                out("zmm21") _,
+                out("zmm22") _,
+                out("zmm23") _,
+                out("zmm24") _,
+                out("zmm25") _,
+                out("zmm26") _,
+                out("zmm27") _,
+                out("zmm28") _,
+                out("zmm29") _,
+                out("zmm30") _,
+                options(nostack),
+            );
+        }
+
+        for (lane, hash) in out.iter_mut().enumerate().take(8) {
+            let mut bytes = [0u8; 32];
+            for (i, word) in words.iter().enumerate() {
+                bytes[i * 8..(i + 1) * 8].copy_from_slice(&word[lane].to_le_bytes());
+            }
+            *hash = KeccakHash::new_from_array(bytes);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use solana_sdk::keccak::Hash as KeccakHash;
+
+use super::Midstate;
+
+pub(super) const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+const RHO: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+
+const PI: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+/// A vector of independent 64-bit keccak lanes, one per message being hashed.
+pub trait Lanes: Copy {
+    const WIDTH: usize;
+
+    fn splat(v: u64) -> Self;
+
+    /// Returns `nonce, nonce + 1, ..` across the vector.
+    fn counter(nonce: u64) -> Self;
+
+    fn xor(self, other: Self) -> Self;
+
+    /// Returns `!self & other`.
+    fn andnot(self, other: Self) -> Self;
+
+    fn rol(self, n: u32) -> Self;
+
+    fn store(self, out: &mut [u64]);
+}
+
+#[inline(always)]
+fn theta<V: Lanes>(a: &mut [V; 25]) {
+    let mut c = [a[0]; 5];
+    for x in 0..5 {
+        c[x] = a[x]
+            .xor(a[x + 5])
+            .xor(a[x + 10])
+            .xor(a[x + 15])
+            .xor(a[x + 20]);
+    }
+    for x in 0..5 {
+        let d = c[(x + 4) % 5].xor(c[(x + 1) % 5].rol(1));
+        for y in 0..5 {
+            a[y * 5 + x] = a[y * 5 + x].xor(d);
+        }
+    }
+}
+
+/// The rest of a keccak-f[1600] round after theta.
+#[inline(always)]
+fn rho_pi_chi_iota<V: Lanes>(a: &mut [V; 25], rc: u64) {
+    // Rho and pi
+    let mut last = a[1];
+    for i in 0..24 {
+        let j = PI[i];
+        let tmp = a[j];
+        a[j] = last.rol(RHO[i]);
+        last = tmp;
+    }
+
+    // Chi
+    for y in 0..5 {
+        let row = [
+            a[y * 5],
+            a[y * 5 + 1],
+            a[y * 5 + 2],
+            a[y * 5 + 3],
+            a[y * 5 + 4],
+        ];
+        for x in 0..5 {
+            a[y * 5 + x] = row[x].xor(row[(x + 1) % 5].andnot(row[(x + 2) % 5]));
+        }
+    }
+
+    // Iota
+    a[0] = a[0].xor(V::splat(rc));
+}
+
+/// Hashes `challenge || pubkey || nonce + i` for every lane of `V`, matching
+/// `keccak::hashv` byte for byte. The first round's theta step is finished from
+/// the precomputed midstate, since only column 3 depends on the nonce.
+#[inline(always)]
+pub fn hash_lanes<V: Lanes>(midstate: &Midstate, nonce: u64, out: &mut [KeccakHash]) {
+    let nonce = V::counter(nonce);
+    let c3 = V::splat(midstate.c3).xor(nonce);
+    let d2 = V::splat(midstate.c1).xor(c3.rol(1));
+    let d4 = c3.xor(V::splat(midstate.c0_rol));
+    let mut a = [V::splat(0); 25];
+    for (i, lane) in midstate.lanes.iter().enumerate() {
+        a[i] = match i % 5 {
+            2 => V::splat(*lane).xor(d2),
+            4 => V::splat(*lane).xor(d4),
+            _ => V::splat(*lane),
+        };
+    }
+    a[8] = a[8].xor(nonce);
+    rho_pi_chi_iota(&mut a, ROUND_CONSTANTS[0]);
+    for rc in &ROUND_CONSTANTS[1..] {
+        theta(&mut a);
+        rho_pi_chi_iota(&mut a, *rc);
+    }
+
+    let mut words = [[0u64; 8]; 4];
+    for (i, word) in words.iter_mut().enumerate() {
+        a[i].store(&mut word[..V::WIDTH]);
+    }
+    for (lane, hash) in out.iter_mut().enumerate().take(V::WIDTH) {
+        let mut bytes = [0u8; 32];
+        for (i, word) in words.iter().enumerate() {
+            bytes[i * 8..(i + 1) * 8].copy_from_slice(&word[lane].to_le_bytes());
+        }
+        *hash = KeccakHash::new_from_array(bytes);
+    }
+}
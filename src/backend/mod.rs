@@ -0,0 +1,193 @@
+#[cfg(target_arch = "x86_64")]
+mod avx2;
+#[cfg(target_arch = "x86_64")]
+mod avx512;
+#[cfg(target_arch = "x86_64")]
+mod keccak;
+mod scalar;
+
+use std::sync::Arc;
+
+use clap::ValueEnum;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::keccak::{Hash as KeccakHash, Hasher};
+
+pub use scalar::ScalarBackend;
+
+/// Computes the mining hash `keccak(challenge || pubkey || nonce)` for a
+/// batch of consecutive nonces.
+pub trait HashBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The number of nonces hashed per call to `hash_batch`.
+    fn lanes(&self) -> usize;
+
+    /// Writes the hash of nonce `nonce + i` to `out[i]` for every `i` in `0..lanes()`.
+    fn hash_batch(&self, midstate: &Midstate, nonce: u64, out: &mut [KeccakHash]);
+}
+
+/// The mining hash input with the constant `challenge || pubkey` prefix
+/// absorbed once per challenge, so only the 8 nonce bytes vary per hash.
+#[derive(Clone)]
+pub struct Midstate {
+    /// Initial keccak state after the first round's theta step, for every lane
+    /// that does not depend on the nonce. Columns 2 and 4 are stored before theta,
+    /// and the nonce lane holds only its theta term.
+    lanes: [u64; 25],
+
+    /// Column parities needed to finish the first theta step once the nonce is known.
+    c1: u64,
+    c3: u64,
+    c0_rol: u64,
+
+    /// Streaming hasher with the prefix already absorbed.
+    hasher: Hasher,
+}
+
+impl Midstate {
+    pub fn new(challenge: &KeccakHash, pubkey: &Pubkey) -> Self {
+        // The 72 byte message fits in one block: 4 challenge lanes, 4 pubkey lanes,
+        // the nonce lane, and keccak padding in lanes 9 and 16.
+        let mut lanes = [0u64; 25];
+        for (i, chunk) in challenge.as_ref().chunks_exact(8).enumerate() {
+            lanes[i] = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+        for (i, chunk) in pubkey.as_ref().chunks_exact(8).enumerate() {
+            lanes[4 + i] = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+        lanes[9] = 0x01;
+        lanes[16] = 0x80 << 56;
+
+        // Only column 3 contains the nonce, so the theta terms for columns 0, 1
+        // and 3 (which read columns 4 and 1, 0 and 2, 2 and 4) are constant.
+        let mut c = [0u64; 5];
+        for (x, c) in c.iter_mut().enumerate() {
+            *c = lanes[x] ^ lanes[x + 5] ^ lanes[x + 10] ^ lanes[x + 15] ^ lanes[x + 20];
+        }
+        for x in [0, 1, 3] {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                lanes[y * 5 + x] ^= d;
+            }
+        }
+
+        let mut hasher = Hasher::default();
+        hasher.hashv(&[challenge.as_ref(), pubkey.as_ref()]);
+        Self {
+            lanes,
+            c1: c[1],
+            c3: c[3],
+            c0_rol: c[0].rotate_left(1),
+            hasher,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BackendKind {
+    /// Pick the widest backend supported by this CPU
+    Auto,
+    /// One nonce at a time
+    Scalar,
+    /// Four nonces at a time using AVX2
+    Avx2,
+    /// Eight nonces at a time using AVX-512
+    Avx512,
+}
+
+/// Returns whether this build includes the backend at all. The SIMD backends are
+/// only built for x86_64.
+pub fn backend_compiled(kind: BackendKind) -> bool {
+    match kind {
+        BackendKind::Auto | BackendKind::Scalar => true,
+        BackendKind::Avx2 | BackendKind::Avx512 => cfg!(target_arch = "x86_64"),
+    }
+}
+
+/// Returns the requested backend, or `None` if it is not available on this CPU or build.
+pub fn hash_backend(kind: BackendKind) -> Option<Arc<dyn HashBackend>> {
+    match kind {
+        BackendKind::Auto => hash_backend(BackendKind::Avx512)
+            .or_else(|| hash_backend(BackendKind::Avx2))
+            .or_else(|| hash_backend(BackendKind::Scalar)),
+        BackendKind::Scalar => Some(Arc::new(ScalarBackend)),
+        BackendKind::Avx2 => avx2_backend(),
+        BackendKind::Avx512 => avx512_backend(),
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn avx2_backend() -> Option<Arc<dyn HashBackend>> {
+    avx2::Avx2Backend::new().map(|b| Arc::new(b) as Arc<dyn HashBackend>)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn avx2_backend() -> Option<Arc<dyn HashBackend>> {
+    None
+}
+
+#[cfg(target_arch = "x86_64")]
+fn avx512_backend() -> Option<Arc<dyn HashBackend>> {
+    avx512::Avx512Backend::new().map(|b| Arc::new(b) as Arc<dyn HashBackend>)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn avx512_backend() -> Option<Arc<dyn HashBackend>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::keccak::hashv;
+
+    use super::*;
+    use crate::mine::validate_hash;
+
+    #[test]
+    fn backends_match_validate_hash() {
+        let mut challenges = vec![
+            KeccakHash::default(),
+            KeccakHash::new_from_array([u8::MAX; 32]),
+        ];
+        let mut pubkeys = vec![Pubkey::default(), Pubkey::new_from_array([u8::MAX; 32])];
+        for i in 0..4u8 {
+            challenges.push(hashv(&[b"challenge", &[i]]));
+            pubkeys.push(Pubkey::new_from_array(hashv(&[b"pubkey", &[i]]).to_bytes()));
+        }
+        let any_difficulty = KeccakHash::new_from_array([u8::MAX; 32]);
+        for kind in [BackendKind::Scalar, BackendKind::Avx2, BackendKind::Avx512] {
+            let Some(backend) = hash_backend(kind) else {
+                continue;
+            };
+            let lanes = backend.lanes() as u64;
+            let nonces = [
+                0,
+                1,
+                0x0123_4567_89ab_cdef,
+                u64::MAX / 2,
+                u64::MAX - lanes - 1,
+                u64::MAX - lanes + 1,
+            ];
+            let mut batch = vec![KeccakHash::default(); backend.lanes()];
+            for challenge in &challenges {
+                for pubkey in &pubkeys {
+                    let midstate = Midstate::new(challenge, pubkey);
+                    for nonce in nonces {
+                        backend.hash_batch(&midstate, nonce, &mut batch);
+                        for (lane, hash) in batch.iter().enumerate() {
+                            let nonce = nonce + lane as u64;
+                            assert!(
+                                validate_hash(*hash, *challenge, *pubkey, nonce, any_difficulty),
+                                "{} backend hashed nonce {} of {} for {} wrong",
+                                backend.name(),
+                                nonce,
+                                challenge,
+                                pubkey
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
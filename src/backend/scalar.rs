@@ -0,0 +1,22 @@
+use solana_sdk::keccak::Hash as KeccakHash;
+
+use super::{HashBackend, Midstate};
+
+/// Hashes one nonce at a time with the same keccak used by the program.
+pub struct ScalarBackend;
+
+impl HashBackend for ScalarBackend {
+    fn name(&self) -> &'static str {
+        "scalar"
+    }
+
+    fn lanes(&self) -> usize {
+        1
+    }
+
+    fn hash_batch(&self, midstate: &Midstate, nonce: u64, out: &mut [KeccakHash]) {
+        let mut hasher = midstate.hasher.clone();
+        hasher.hash(nonce.to_le_bytes().as_slice());
+        out[0] = hasher.result();
+    }
+}
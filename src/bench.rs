@@ -0,0 +1,145 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::keccak::{hashv, Hash as KeccakHash};
+
+use crate::{
+    backend::HashBackend,
+    workers::{nonce_ranges, WorkerOptions, WorkerPool},
+    Miner, OutputFormat,
+};
+
+// How long each thread count is measured for when calibrating `--threads auto`
+const CALIBRATION_DURATION: Duration = Duration::from_millis(500);
+
+#[derive(Serialize)]
+struct BenchReport {
+    backend: &'static str,
+    duration_secs: u64,
+    results: Vec<BenchResult>,
+    recommended_threads: u64,
+}
+
+#[derive(Serialize)]
+struct BenchResult {
+    threads: u64,
+    hashes: u64,
+    hashes_per_sec: f64,
+    efficiency: f64,
+}
+
+impl Miner {
+    pub fn bench(
+        &self,
+        threads: u64,
+        duration: u64,
+        backend: Arc<dyn HashBackend>,
+        format: OutputFormat,
+    ) {
+        let pool = self.worker_pool(&backend, threads, WorkerOptions::default());
+        let mut results: Vec<BenchResult> = vec![];
+        for n in 1..=threads {
+            if let OutputFormat::Table = format {
+                eprintln!("Measuring {} thread(s)...", n);
+            }
+            let (hashes, elapsed) = measure_hash_rate(&pool, n, Duration::from_secs(duration));
+            let hashes_per_sec = hashes as f64 / elapsed.as_secs_f64();
+            let single = results.first().map_or(hashes_per_sec, |r| r.hashes_per_sec);
+            results.push(BenchResult {
+                threads: n,
+                hashes,
+                hashes_per_sec,
+                efficiency: hashes_per_sec / (single * n as f64),
+            });
+        }
+        let report = BenchReport {
+            backend: pool.backend().name(),
+            duration_secs: duration,
+            recommended_threads: best_thread_count(&results),
+            results,
+        };
+
+        match format {
+            OutputFormat::Table => {
+                println!("Backend: {}", report.backend);
+                println!(
+                    "{:>8}  {:>14}  {:>8}  {:>10}",
+                    "Threads", "Hashes/sec", "Speedup", "Efficiency"
+                );
+                for r in report.results.iter() {
+                    println!(
+                        "{:>8}  {:>14.0}  {:>7.2}x  {:>9.1}%",
+                        r.threads,
+                        r.hashes_per_sec,
+                        r.efficiency * r.threads as f64,
+                        r.efficiency * 100.0
+                    );
+                }
+                println!("Recommended: --threads {}", report.recommended_threads);
+            }
+            OutputFormat::Json => {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            }
+        }
+    }
+}
+
+/// Runs the mining search loop against a synthetic challenge that can never be
+/// solved, and returns the number of hashes computed before `duration` elapsed.
+pub fn measure_hash_rate(pool: &WorkerPool, threads: u64, duration: Duration) -> (u64, Duration) {
+    let challenge = hashv(&[b"ore-bench"]);
+    let pubkey = Pubkey::new_unique();
+    let impossible = KeccakHash::new_from_array([0; 32]);
+    let stop = Arc::new(AtomicBool::new(false));
+    let hashes = Arc::new(AtomicU64::new(0));
+    let timer = std::thread::spawn({
+        let stop = stop.clone();
+        move || {
+            std::thread::sleep(duration);
+            stop.store(true, Ordering::Relaxed);
+        }
+    });
+    let start = Instant::now();
+    pool.search(
+        challenge,
+        pubkey,
+        impossible,
+        Arc::new(nonce_ranges(threads)),
+        stop,
+        hashes.clone(),
+    );
+    let elapsed = start.elapsed();
+    timer.join().unwrap();
+    (hashes.load(Ordering::Relaxed), elapsed)
+}
+
+/// Measures every thread count the pool can run for a short while and returns
+/// the one with the highest throughput.
+pub fn calibrate_threads(pool: &WorkerPool) -> u64 {
+    (1..=pool.size())
+        .map(|n| {
+            let (hashes, elapsed) = measure_hash_rate(pool, n, CALIBRATION_DURATION);
+            (n, hashes as f64 / elapsed.as_secs_f64())
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(1, |(n, _)| n)
+}
+
+/// The number of CPUs available to this process.
+pub fn available_threads() -> u64 {
+    std::thread::available_parallelism().map_or(1, |n| n.get() as u64)
+}
+
+fn best_thread_count(results: &[BenchResult]) -> u64 {
+    results
+        .iter()
+        .max_by(|a, b| a.hashes_per_sec.total_cmp(&b.hashes_per_sec))
+        .map_or(1, |r| r.threads)
+}
@@ -8,19 +8,21 @@ impl Miner {
         let client = self.rpc_client.clone();
         for address in BUS_ADDRESSES.iter() {
             let data = client.get_account_data(address).await.unwrap();
-            match Bus::try_from_bytes(&data) {
-                Ok(bus) => {
-                    let rewards = (bus.rewards as f64) / 10f64.powf(TOKEN_DECIMALS as f64);
-                    println!("Bus {}: {:} ORE", bus.id, rewards);
-                }
-                Err(_) => {}
+            if let Ok(bus) = Bus::try_from_bytes(&data) {
+                let rewards = (bus.rewards as f64) / 10f64.powf(TOKEN_DECIMALS as f64);
+                println!("Bus {}: {:} ORE", bus.id, rewards);
             }
         }
     }
 
-    pub async fn get_bus(&self, id: usize) -> Result<Bus> {
+    /// Fetches every bus in one request.
+    pub async fn get_busses(&self) -> Result<Vec<Bus>> {
         let client = self.rpc_client.clone();
-        let data = client.get_account_data(&BUS_ADDRESSES[id]).await?;
-        Ok(*Bus::try_from_bytes(&data).unwrap())
+        let accounts = client.get_multiple_accounts(&BUS_ADDRESSES).await?;
+        Ok(accounts
+            .into_iter()
+            .flatten()
+            .filter_map(|account| Bus::try_from_bytes(&account.data).ok().copied())
+            .collect())
     }
 }
@@ -0,0 +1,251 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::keccak::Hash as KeccakHash;
+
+use crate::{
+    events::{emit, Event},
+    workers::{nonce_ranges, NonceRange},
+    Miner,
+};
+
+// How often search progress is written to disk
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The unsearched `(next, end)` nonce ranges of one authority's challenge, and
+/// the nonce of a solution that was found but has not been submitted yet.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    authority: String,
+    challenge: String,
+    ranges: Vec<(u64, u64)>,
+    #[serde(default)]
+    solution: Option<u64>,
+}
+
+/// Where the last search for a challenge left off.
+pub struct SavedSearch {
+    pub ranges: Vec<NonceRange>,
+
+    /// Nonce of a solution that was found but not yet submitted
+    pub solution: Option<u64>,
+}
+
+impl Miner {
+    pub fn checkpoint_path(&self, authority: Pubkey) -> PathBuf {
+        self.data_dir
+            .join("checkpoints")
+            .join(format!("{}.json", authority))
+    }
+
+    /// Returns where the last search for `authority` left off if it was on the
+    /// same challenge, with its ranges spread over `threads` workers.
+    pub fn load_checkpoint(
+        &self,
+        authority: Pubkey,
+        challenge: KeccakHash,
+        threads: u64,
+    ) -> Option<SavedSearch> {
+        let checkpoint = read_checkpoint(&self.checkpoint_path(authority))?;
+        if checkpoint.authority.ne(&authority.to_string())
+            || checkpoint.challenge.ne(&challenge.to_string())
+        {
+            return None;
+        }
+        if checkpoint.ranges.len() as u64 != threads {
+            self.log(format!(
+                "Checkpoint was saved with {} threads, redistributing its ranges",
+                checkpoint.ranges.len()
+            ));
+        }
+        Some(SavedSearch {
+            ranges: fit_ranges(checkpoint.ranges, threads as usize),
+            solution: checkpoint.solution,
+        })
+    }
+
+    /// Forgets the pending solution of `authority`'s checkpoint once it has
+    /// been submitted.
+    pub fn clear_solution(&self, authority: Pubkey) {
+        let path = self.checkpoint_path(authority);
+        let Some(mut checkpoint) = read_checkpoint(&path) else {
+            return;
+        };
+        if checkpoint.solution.take().is_some() {
+            if let Err(err) = write_checkpoint(&path, &checkpoint) {
+                self.error(format!("Failed to save checkpoint: {}", err));
+            }
+        }
+    }
+}
+
+fn read_checkpoint(path: &Path) -> Option<Checkpoint> {
+    let data = fs::read(path).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// Turns the unsearched spans of a checkpoint into `parts` ranges. Spans are
+/// halved, largest first, while there are too few, and the smallest are
+/// dropped if there are too many: skipping nonces is harmless, as any nonce is
+/// as likely as another to be a solution.
+fn fit_ranges(mut spans: Vec<(u64, u64)>, parts: usize) -> Vec<NonceRange> {
+    spans.retain(|(next, end)| next < end);
+    if spans.is_empty() {
+        return nonce_ranges(parts as u64);
+    }
+    spans.sort_by_key(|(next, end)| std::cmp::Reverse(end - next));
+    spans.truncate(parts);
+    while spans.len() < parts {
+        let (next, end) = spans[0];
+        if end - next < 2 {
+            break;
+        }
+        let mid = next + (end - next) / 2;
+        spans[0] = (next, mid);
+        spans.push((mid, end));
+        spans.sort_by_key(|(next, end)| std::cmp::Reverse(end - next));
+    }
+    spans
+        .into_iter()
+        .map(|(next, end)| NonceRange {
+            next: AtomicU64::new(next),
+            end,
+        })
+        .collect()
+}
+
+/// Writes the unsearched part of each worker range for `challenge` to `path`,
+/// along with a found `solution` that is yet to be submitted, replacing any
+/// earlier checkpoint.
+pub fn save_checkpoint(
+    path: &Path,
+    authority: Pubkey,
+    challenge: KeccakHash,
+    ranges: &[NonceRange],
+    solution: Option<u64>,
+) -> io::Result<()> {
+    let checkpoint = Checkpoint {
+        authority: authority.to_string(),
+        challenge: challenge.to_string(),
+        ranges: ranges
+            .iter()
+            .map(|r| (r.next.load(Ordering::Relaxed), r.end))
+            .collect(),
+        solution,
+    };
+    write_checkpoint(path, &checkpoint)
+}
+
+fn write_checkpoint(path: &Path, checkpoint: &Checkpoint) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Write to a temporary file first so a crash never leaves a truncated checkpoint.
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec(checkpoint)?)?;
+    fs::rename(tmp, path)
+}
+
+/// Periodically saves the worker ranges until the task is aborted. With
+/// `events`, failures are reported as `error` events.
+pub async fn save_checkpoints(
+    path: PathBuf,
+    authority: Pubkey,
+    challenge: KeccakHash,
+    ranges: Arc<Vec<NonceRange>>,
+    events: bool,
+) {
+    loop {
+        tokio::time::sleep(CHECKPOINT_INTERVAL).await;
+        if let Err(err) = save_checkpoint(&path, authority, challenge, &ranges, None) {
+            let message = format!("Failed to save checkpoint: {}", err);
+            if events {
+                emit(Some(authority), &Event::Error { message });
+            } else {
+                println!("\n{}", message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(ranges: Vec<NonceRange>) -> Vec<(u64, u64)> {
+        let mut spans: Vec<(u64, u64)> = ranges
+            .into_iter()
+            .map(|r| (r.next.into_inner(), r.end))
+            .collect();
+        spans.sort();
+        spans
+    }
+
+    fn assert_disjoint(spans: &[(u64, u64)]) {
+        for pair in spans.windows(2) {
+            assert!(pair[0].1 <= pair[1].0, "{:?} overlap", pair);
+        }
+    }
+
+    #[test]
+    fn keeps_ranges_for_the_same_thread_count() {
+        let saved = vec![(10, 100), (150, 200), (300, 400)];
+        assert_eq!(spans(fit_ranges(saved.clone(), 3)), saved);
+    }
+
+    #[test]
+    fn splits_the_largest_ranges_for_more_threads() {
+        let fitted = spans(fit_ranges(vec![(0, 100), (200, 220)], 4));
+        assert_eq!(fitted, vec![(0, 25), (25, 50), (50, 100), (200, 220)]);
+        assert_disjoint(&fitted);
+    }
+
+    #[test]
+    fn drops_the_smallest_ranges_for_fewer_threads() {
+        let fitted = spans(fit_ranges(vec![(0, 10), (100, 1000), (2000, 2500)], 2));
+        assert_eq!(fitted, vec![(100, 1000), (2000, 2500)]);
+    }
+
+    #[test]
+    fn skips_searched_ranges() {
+        let fitted = spans(fit_ranges(vec![(50, 50), (60, 80)], 2));
+        assert_eq!(fitted, vec![(60, 70), (70, 80)]);
+    }
+
+    #[test]
+    fn stops_splitting_single_nonces() {
+        assert_eq!(spans(fit_ranges(vec![(7, 8)], 4)), vec![(7, 8)]);
+    }
+
+    #[test]
+    fn starts_over_once_every_range_is_searched() {
+        let fitted = spans(fit_ranges(vec![(100, 100), (u64::MAX, u64::MAX)], 2));
+        assert_eq!(fitted, spans(nonce_ranges(2)));
+    }
+
+    #[test]
+    fn resumed_ranges_never_overlap() {
+        let saved = spans(nonce_ranges(3));
+        let saved: Vec<(u64, u64)> = saved
+            .into_iter()
+            .map(|(next, end)| (next + (end - next) / 3, end))
+            .collect();
+        for threads in 1..=8 {
+            let fitted = spans(fit_ranges(saved.clone(), threads));
+            assert_eq!(fitted.len(), threads);
+            assert_disjoint(&fitted);
+            for (next, end) in fitted {
+                assert!(saved.iter().any(|(s, e)| *s <= next && end <= *e));
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::time::{Duration, Instant};
+
+use futures::{stream::select_all, StreamExt};
+use solana_client::{
+    nonblocking::pubsub_client::{PubsubClient, PubsubClientError},
+    rpc_config::RpcSignatureSubscribeConfig,
+    rpc_response::RpcSignatureResult,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, signature::Signature, transaction::TransactionError,
+};
+use solana_transaction_status::TransactionConfirmationStatus;
+
+use crate::Miner;
+
+// How often signature statuses are polled when they cannot be subscribed to
+const POLL_INTERVAL: Duration = Duration::from_millis(5000);
+
+// How long connecting to `--ws` and subscribing may take
+const SUBSCRIBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The index of the signature that was confirmed, and its status, or the error
+/// its transaction failed with on chain.
+pub type Confirmation = (
+    usize,
+    Result<TransactionConfirmationStatus, TransactionError>,
+);
+
+impl Miner {
+    /// Waits up to `timeout` for any of `signatures` to be confirmed, and
+    /// returns its index and status. A transaction that was included but failed
+    /// is returned with its error. Listens for `signatureSubscribe`
+    /// notifications on `--ws`, and polls signature statuses instead if the
+    /// subscription fails.
+    pub async fn confirm(
+        &self,
+        signatures: &[Signature],
+        timeout: Duration,
+    ) -> Option<Confirmation> {
+        let deadline = Instant::now() + timeout;
+        match self.subscribe_confirmation(signatures, deadline).await {
+            Ok(confirmed) => confirmed,
+            Err(err) => {
+                self.error(format!(
+                    "Signature subscription failed, polling instead: {}",
+                    err
+                ));
+                self.poll_confirmation(signatures, deadline).await
+            }
+        }
+    }
+
+    async fn subscribe_confirmation(
+        &self,
+        signatures: &[Signature],
+        deadline: Instant,
+    ) -> Result<Option<Confirmation>, PubsubClientError> {
+        // An unresponsive endpoint must leave time to poll instead
+        let setup_deadline = deadline.min(Instant::now() + SUBSCRIBE_TIMEOUT).into();
+        let timed_out =
+            |_| PubsubClientError::RequestError(format!("no answer in {:?}", SUBSCRIBE_TIMEOUT));
+        let pubsub = tokio::time::timeout_at(setup_deadline, PubsubClient::new(&self.ws_url))
+            .await
+            .map_err(timed_out)??;
+        let config = RpcSignatureSubscribeConfig {
+            commitment: Some(CommitmentConfig::confirmed()),
+            enable_received_notification: Some(false),
+        };
+        // Subscriptions end with the connection, so there is no need to
+        // unsubscribe from them
+        let subscribe = async {
+            let mut subscriptions = vec![];
+            for (i, signature) in signatures.iter().enumerate() {
+                let (stream, _unsubscribe) = pubsub
+                    .signature_subscribe(signature, Some(config.clone()))
+                    .await?;
+                subscriptions.push(stream.map(move |response| (i, response.value)));
+            }
+            Ok::<_, PubsubClientError>(subscriptions)
+        };
+        let subscriptions = tokio::time::timeout_at(setup_deadline, subscribe)
+            .await
+            .map_err(timed_out)??;
+        let mut notifications = select_all(subscriptions);
+
+        // A signature confirmed before it was subscribed to is never notified
+        let confirmed = match self.poll_statuses(signatures).await {
+            Some(confirmed) => Some(confirmed),
+            None => {
+                let notified = tokio::time::timeout_at(deadline.into(), async {
+                    while let Some((i, result)) = notifications.next().await {
+                        if let RpcSignatureResult::ProcessedSignature(processed) = result {
+                            return Some(match processed.err {
+                                Some(err) => (i, Err(err)),
+                                None => (i, Ok(TransactionConfirmationStatus::Confirmed)),
+                            });
+                        }
+                    }
+                    None
+                })
+                .await;
+                match notified {
+                    Ok(Some(confirmed)) => Some(confirmed),
+                    // The connection closed early
+                    Ok(None) => self.poll_confirmation(signatures, deadline).await,
+                    Err(_) => None,
+                }
+            }
+        };
+        drop(notifications);
+        tokio::time::timeout(SUBSCRIBE_TIMEOUT, pubsub.shutdown())
+            .await
+            .ok();
+        Ok(confirmed)
+    }
+
+    /// Polls the statuses of `signatures` until one is confirmed or `deadline`
+    /// passes.
+    async fn poll_confirmation(
+        &self,
+        signatures: &[Signature],
+        deadline: Instant,
+    ) -> Option<Confirmation> {
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return None;
+            }
+            tokio::time::sleep(POLL_INTERVAL.min(remaining)).await;
+            if let Some(confirmed) = self.poll_statuses(signatures).await {
+                return Some(confirmed);
+            }
+        }
+    }
+
+    /// Fetches the statuses of `signatures` once, and returns the first that is
+    /// confirmed or finalized, or that failed.
+    pub async fn poll_statuses(&self, signatures: &[Signature]) -> Option<Confirmation> {
+        match self.rpc_client.get_signature_statuses(signatures).await {
+            Ok(signature_statuses) => {
+                self.log(format!(
+                    "Confirmation: {:?}",
+                    signature_statuses.value.last().unwrap()
+                ));
+                signature_statuses
+                    .value
+                    .into_iter()
+                    .enumerate()
+                    .find_map(|(i, status)| {
+                        let status = status?;
+                        if let Some(err) = status.err {
+                            return Some((i, Err(err)));
+                        }
+                        match status.confirmation_status? {
+                            TransactionConfirmationStatus::Processed => None,
+                            status => Some((i, Ok(status))),
+                        }
+                    })
+            }
+
+            // Handle confirmation errors
+            Err(err) => {
+                self.error(format!("{:?}", err.kind().to_string()));
+                None
+            }
+        }
+    }
+}
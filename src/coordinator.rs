@@ -0,0 +1,149 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{keccak::Hash as KeccakHash, signature::Signer};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, watch},
+};
+
+use crate::{
+    dispatch::{accept_connections, RangeAllocator, Solution},
+    protocol::{read_message, write_message, LineReader, Message},
+    solve::parse_hex_hash,
+    Miner,
+};
+
+/// The challenge currently being searched, and the nonces handed out for it.
+struct Assignment {
+    challenge: KeccakHash,
+    authority: Pubkey,
+    difficulty: KeccakHash,
+    ranges: RangeAllocator,
+}
+
+impl Assignment {
+    /// Hands out the next disjoint nonce range for this challenge, if any are left.
+    fn next_work(&self) -> Option<Message> {
+        let (start, end) = self.ranges.next_range()?;
+        Some(Message::Work {
+            challenge: hex::encode(self.challenge),
+            authority: self.authority.to_string(),
+            difficulty: hex::encode(self.difficulty),
+            start,
+            end,
+        })
+    }
+}
+
+impl Miner {
+    /// Hands out nonce ranges for this keypair's challenge to remote workers,
+    /// and submits the solutions they find.
+    pub async fn coordinator(&self, listen: String) {
+        self.register().await;
+        let signer = self.signer();
+        let listener = TcpListener::bind(&listen)
+            .await
+            .expect("Failed to bind listen address");
+        println!("Listening for workers on {}", listen);
+
+        // Accept workers in the background
+        let (work_tx, work_rx) = watch::channel::<Option<Arc<Assignment>>>(None);
+        let (solution_tx, solution_rx) = mpsc::unbounded_channel();
+        let connected = Arc::new(AtomicUsize::new(0));
+        accept_connections(listener, {
+            let connected = connected.clone();
+            move |stream, addr| {
+                handle_worker(
+                    stream,
+                    addr,
+                    work_rx.clone(),
+                    solution_tx.clone(),
+                    connected.clone(),
+                )
+            }
+        });
+
+        self.serve_challenges(
+            solution_rx,
+            |challenge, difficulty| {
+                work_tx.send_replace(Some(Arc::new(Assignment {
+                    challenge,
+                    authority: signer.pubkey(),
+                    difficulty,
+                    ranges: RangeAllocator::default(),
+                })));
+                println!(
+                    "Challenge {} sent to {} worker(s)",
+                    challenge,
+                    connected.load(Ordering::Relaxed)
+                );
+            },
+            || {},
+        )
+        .await;
+    }
+}
+
+/// Sends work to one connected worker whenever the challenge changes or its
+/// range runs out, and forwards its solutions to the coordinator.
+async fn handle_worker(
+    stream: TcpStream,
+    addr: SocketAddr,
+    mut work_rx: watch::Receiver<Option<Arc<Assignment>>>,
+    solutions: mpsc::UnboundedSender<Solution>,
+    connected: Arc<AtomicUsize>,
+) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = LineReader::new(reader);
+    connected.fetch_add(1, Ordering::Relaxed);
+    loop {
+        let work = tokio::select! {
+            changed = work_rx.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                work_rx.borrow_and_update().as_ref().and_then(|a| a.next_work())
+            }
+            message = read_message(&mut lines) => match message {
+                Ok(Some(Message::Hello { threads })) => {
+                    println!("Worker {} connected with {} threads", addr, threads);
+                    work_rx.borrow_and_update().as_ref().and_then(|a| a.next_work())
+                }
+                Ok(Some(Message::Exhausted)) => {
+                    work_rx.borrow().as_ref().and_then(|a| a.next_work())
+                }
+                Ok(Some(Message::Solution { challenge, nonce, hash })) => {
+                    let current = work_rx.borrow().as_ref().map(|a| a.challenge);
+                    match (parse_hex_hash(&challenge), parse_hex_hash(&hash)) {
+                        (Ok(challenge), Ok(hash)) if Some(challenge).eq(&current) => {
+                            println!("Worker {} found a solution", addr);
+                            solutions.send((challenge, hash, nonce)).ok();
+                        }
+                        _ => println!("Worker {} sent a stale or malformed solution", addr),
+                    }
+                    None
+                }
+                Ok(Some(_)) => None,
+                Ok(None) => break,
+                Err(err) => {
+                    println!("Worker {} sent an invalid message: {}", addr, err);
+                    break;
+                }
+            },
+        };
+        if let Some(work) = work {
+            if write_message(&mut writer, &work).await.is_err() {
+                break;
+            }
+        }
+    }
+    connected.fetch_sub(1, Ordering::Relaxed);
+    println!("Worker {} disconnected", addr);
+}
@@ -0,0 +1,148 @@
+use std::{
+    future::Future,
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use solana_sdk::{keccak::Hash as KeccakHash, signature::Signer};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    time::{Instant, MissedTickBehavior},
+};
+
+use crate::{
+    utils::{get_proof, get_treasury},
+    Miner,
+};
+
+// Number of nonces handed to a remote searcher at a time
+const RANGE_SIZE: u64 = 1 << 40;
+
+// How often to check whether the challenge changed during a search
+pub const CHALLENGE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// How long to wait before accepting connections again after a failure
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// A hash found for a challenge, and its nonce.
+pub type Solution = (KeccakHash, KeccakHash, u64);
+
+/// Hands out disjoint nonce ranges for one challenge.
+#[derive(Default)]
+pub struct RangeAllocator {
+    next: AtomicU64,
+}
+
+impl RangeAllocator {
+    /// Returns the next unassigned `(start, end)` range, or `None` once the
+    /// whole nonce space has been handed out.
+    pub fn next_range(&self) -> Option<(u64, u64)> {
+        // The last range ends at u64::MAX, which then marks the space as used up
+        let start = self
+            .next
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| {
+                (next < u64::MAX).then(|| next.checked_add(RANGE_SIZE).unwrap_or(u64::MAX))
+            })
+            .ok()?;
+        Some((start, start.checked_add(RANGE_SIZE).unwrap_or(u64::MAX)))
+    }
+}
+
+/// Accepts connections on `listener` in the background, running `handle` for
+/// each one on its own task.
+pub fn accept_connections<F, Fut>(listener: TcpListener, handle: F)
+where
+    F: Fn(TcpStream, SocketAddr) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    tokio::spawn(handle(stream, addr));
+                }
+                // Errors such as running out of file descriptors last a while
+                Err(err) => {
+                    println!("Failed to accept a connection: {}", err);
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                }
+            }
+        }
+    });
+}
+
+impl Miner {
+    /// Mines this keypair's challenge with remote searchers. Each new challenge
+    /// and its difficulty are passed to `publish`, and the first valid solution
+    /// that comes back on `solutions` is submitted before `landed` is called.
+    /// The search restarts whenever the challenge changes on chain.
+    pub async fn serve_challenges(
+        &self,
+        mut solutions: mpsc::UnboundedReceiver<Solution>,
+        mut publish: impl FnMut(KeccakHash, KeccakHash),
+        mut landed: impl FnMut(),
+    ) {
+        let signer = self.signer();
+        'mine: loop {
+            // Publish the current challenge
+            let treasury = get_treasury(&self.rpc_client).await;
+            let proof = get_proof(&self.rpc_client, signer.pubkey()).await;
+            let challenge: KeccakHash = proof.hash.into();
+            let difficulty: KeccakHash = treasury.difficulty.into();
+            publish(challenge, difficulty);
+
+            // Wait for a valid solution, or restart if the challenge changes. The
+            // challenge is checked on schedule however many solutions arrive.
+            let mut poll = tokio::time::interval_at(
+                Instant::now() + CHALLENGE_POLL_INTERVAL,
+                CHALLENGE_POLL_INTERVAL,
+            );
+            poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let (next_hash, nonce) = loop {
+                tokio::select! {
+                    Some((solved, next_hash, nonce)) = solutions.recv() => {
+                        if solved.ne(&challenge) {
+                            continue;
+                        }
+                        if self.validate_hash(next_hash, challenge, signer.pubkey(), nonce, difficulty) {
+                            break (next_hash, nonce);
+                        }
+                        println!("Rejected invalid solution {} (nonce {})", next_hash, nonce);
+                    }
+                    _ = poll.tick() => {
+                        let proof = get_proof(&self.rpc_client, signer.pubkey()).await;
+                        if KeccakHash::from(proof.hash).ne(&challenge) {
+                            println!("Challenge changed on chain, restarting search...");
+                            continue 'mine;
+                        }
+                    }
+                }
+            };
+
+            println!("Submitting hash for validation...");
+            self.land_hash(next_hash, nonce, difficulty).await;
+            landed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_stop_at_the_end_of_the_nonce_space() {
+        let ranges = RangeAllocator::default();
+        assert_eq!(ranges.next_range(), Some((0, RANGE_SIZE)));
+        assert_eq!(ranges.next_range(), Some((RANGE_SIZE, 2 * RANGE_SIZE)));
+
+        // The last range is cut short so its end fits in a u64
+        let last = 0u64.wrapping_sub(RANGE_SIZE);
+        ranges.next.store(last, Ordering::Relaxed);
+        assert_eq!(ranges.next_range(), Some((last, u64::MAX)));
+        assert_eq!(ranges.next_range(), None);
+        assert_eq!(ranges.next_range(), None);
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde_json::Value;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_config::RpcSendTransactionConfig,
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, signature::Signature, transaction::Transaction,
+};
+use tokio::sync::Mutex;
+
+use crate::{events, events::Event, Miner};
+
+// How long an endpoint has to answer a health check
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+// How often the read endpoints are probed to pick the fastest
+const REPROBE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Returns a client that reads from the fastest healthy endpoint of `urls`, and
+/// the URL it starts with. With several endpoints, they are probed again every
+/// `REPROBE_INTERVAL` and whenever a request fails to reach the current one, and
+/// reads move to whichever is fastest.
+pub async fn read_client(urls: Vec<String>, events: bool) -> (RpcClient, String) {
+    let commitment = CommitmentConfig::finalized();
+    if urls.len() == 1 {
+        let url = urls[0].clone();
+        return (RpcClient::new_with_commitment(url.clone(), commitment), url);
+    }
+    let endpoints = Arc::new(ReadEndpoints {
+        clients: urls
+            .iter()
+            .map(|url| RpcClient::new_with_commitment(url.clone(), commitment))
+            .collect(),
+        urls,
+        current: AtomicUsize::new(0),
+        probed: AtomicBool::new(false),
+        probing: Mutex::new(()),
+        events,
+    });
+    endpoints.probe().await;
+    tokio::spawn(reprobe_endpoints(endpoints.clone()));
+    let url = endpoints.current_url();
+    let client = RpcClient::new_sender(
+        FailoverSender(endpoints),
+        RpcClientConfig::with_commitment(commitment),
+    );
+    (client, url)
+}
+
+/// The `--rpc` endpoints, and which of them is read from.
+struct ReadEndpoints {
+    urls: Vec<String>,
+    clients: Vec<RpcClient>,
+    current: AtomicUsize,
+
+    /// Whether the endpoints have been probed yet, so the first pick is logged
+    probed: AtomicBool,
+
+    /// Held while the endpoints are probed, so failing requests don't all probe at once
+    probing: Mutex<()>,
+    events: bool,
+}
+
+impl ReadEndpoints {
+    fn current_url(&self) -> String {
+        self.urls[self.current.load(Ordering::Relaxed)].clone()
+    }
+
+    /// Health checks every endpoint and reads from the fastest healthy one, or
+    /// from the current one if none are healthy.
+    async fn probe(&self) {
+        let Ok(_probing) = self.probing.try_lock() else {
+            return;
+        };
+        let checks = self.urls.iter().map(|url| async move {
+            let client = RpcClient::new(url.clone());
+            let timer = Instant::now();
+            let health = tokio::time::timeout(HEALTH_TIMEOUT, client.get_health()).await;
+            let latency = timer.elapsed();
+            match health {
+                Ok(Ok(())) => {
+                    events::log(
+                        self.events,
+                        format!(
+                            "RPC {}: healthy in {} ms",
+                            endpoint_name(url),
+                            latency.as_millis()
+                        ),
+                    );
+                    Some(latency)
+                }
+                Ok(Err(err)) => {
+                    events::log(
+                        self.events,
+                        format!(
+                            "RPC {}: unhealthy: {}",
+                            endpoint_name(url),
+                            describe_error(&err)
+                        ),
+                    );
+                    None
+                }
+                Err(_) => {
+                    events::log(
+                        self.events,
+                        format!(
+                            "RPC {}: no answer in {:?}",
+                            endpoint_name(url),
+                            HEALTH_TIMEOUT
+                        ),
+                    );
+                    None
+                }
+            }
+        });
+        let latencies = join_all(checks).await;
+        let fastest = latencies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, latency)| latency.map(|latency| (i, latency)))
+            .min_by_key(|(_, latency)| *latency)
+            .map_or(self.current.load(Ordering::Relaxed), |(i, _)| i);
+        let previous = self.current.swap(fastest, Ordering::Relaxed);
+        if previous != fastest || !self.probed.swap(true, Ordering::Relaxed) {
+            events::log(
+                self.events,
+                format!("Reading from {}", endpoint_name(&self.urls[fastest])),
+            );
+        }
+    }
+}
+
+/// Probes the read endpoints every `REPROBE_INTERVAL` until the process exits.
+async fn reprobe_endpoints(endpoints: Arc<ReadEndpoints>) {
+    loop {
+        tokio::time::sleep(REPROBE_INTERVAL).await;
+        endpoints.probe().await;
+    }
+}
+
+/// Sends each request to the current read endpoint. A request that cannot reach
+/// it triggers a probe, and is retried once if reads move to another endpoint.
+struct FailoverSender(Arc<ReadEndpoints>);
+
+#[async_trait]
+impl RpcSender for FailoverSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let endpoints = &self.0;
+        let current = endpoints.current.load(Ordering::Relaxed);
+        let result = endpoints.clients[current]
+            .send(request, params.clone())
+            .await;
+        match &result {
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ClientErrorKind::Reqwest(_) | ClientErrorKind::Io(_)
+                ) =>
+            {
+                events::log(
+                    endpoints.events,
+                    format!(
+                        "RPC {}: {}",
+                        endpoint_name(&endpoints.urls[current]),
+                        describe_error(err)
+                    ),
+                );
+                endpoints.probe().await;
+                let next = endpoints.current.load(Ordering::Relaxed);
+                if next == current {
+                    return result;
+                }
+                endpoints.clients[next].send(request, params).await
+            }
+            _ => result,
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        self.0.current_url()
+    }
+}
+
+/// The host of an RPC URL, which is safe to print unlike the API keys paid
+/// endpoints put in paths and query strings.
+pub fn endpoint_name(url: &str) -> &str {
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    url.split(['/', '?']).next().unwrap_or(url)
+}
+
+/// Describes an RPC error without the URL of the endpoint.
+fn describe_error(err: &ClientError) -> String {
+    let message = err.kind().to_string();
+    match err.kind() {
+        ClientErrorKind::Reqwest(err) => match err.url() {
+            Some(url) => message.replace(url.as_str(), endpoint_name(url.as_str())),
+            None => message,
+        },
+        _ => message,
+    }
+}
+
+impl Miner {
+    /// Sends a signed transaction to every `--send-rpc` endpoint at once and
+    /// reports how each one did. Succeeds if any endpoint accepted it.
+    pub async fn broadcast(
+        &self,
+        tx: &Transaction,
+        config: RpcSendTransactionConfig,
+    ) -> ClientResult<Signature> {
+        let sends = self.send_clients.iter().map(|client| async move {
+            let timer = Instant::now();
+            let result = client.send_transaction_with_config(tx, config).await;
+            (client.url(), timer.elapsed(), result)
+        });
+        let mut signature = None;
+        let mut error = None;
+        for (url, latency, result) in join_all(sends).await {
+            let endpoint = endpoint_name(&url).to_string();
+            let latency_ms = latency.as_millis() as u64;
+            match result {
+                Ok(sig) => {
+                    self.log(format!("  Sent via {} in {} ms", endpoint, latency_ms));
+                    self.emit(Event::Broadcast {
+                        endpoint,
+                        latency_ms,
+                        error: None,
+                    });
+                    signature.get_or_insert(sig);
+                }
+                Err(err) => {
+                    let message = describe_error(&err);
+                    self.log(format!(
+                        "  Failed via {} after {} ms: {}",
+                        endpoint, latency_ms, message
+                    ));
+                    self.emit(Event::Broadcast {
+                        endpoint,
+                        latency_ms,
+                        error: Some(message),
+                    });
+                    error.get_or_insert(err);
+                }
+            }
+        }
+        match (signature, error) {
+            (Some(sig), _) => Ok(sig),
+            (None, Some(err)) => Err(err),
+            (None, None) => Err(ClientError {
+                request: None,
+                kind: ClientErrorKind::Custom("No send endpoints".into()),
+            }),
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt::Display;
+
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+use crate::{session::SessionReport, Miner};
+
+/// A state transition of `ore mine`, written as one JSON object per line with
+/// `--events jsonl`.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    StateFetched {
+        challenge: String,
+        difficulty: String,
+        reward_rate: u64,
+        claimable_rewards: u64,
+    },
+    SearchStarted {
+        challenge: String,
+        threads: u64,
+        resumed: bool,
+    },
+    ChallengeChanged {
+        challenge: String,
+    },
+    SolutionFound {
+        hash: String,
+        nonce: u64,
+        hashes: u64,
+        secs: f64,
+    },
+    ResetSent,
+    BussesExhausted {
+        reset_at: i64,
+    },
+    BusChosen {
+        bus: u64,
+        rewards: u64,
+    },
+    Broadcast {
+        endpoint: String,
+        latency_ms: u64,
+        error: Option<String>,
+    },
+    TxSent {
+        signature: String,
+        attempt: usize,
+
+        /// Microlamports per compute unit
+        compute_unit_price: u64,
+
+        /// Lamports the transaction pays if it lands
+        fee: u64,
+    },
+    TxConfirmed {
+        signature: String,
+        status: String,
+    },
+    ScheduleChanged {
+        open: bool,
+    },
+    Error {
+        message: String,
+    },
+    SessionEnded(SessionReport),
+}
+
+#[derive(Serialize)]
+struct EventLine<'a> {
+    timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    authority: Option<String>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// Writes an event to stdout as a line of JSON.
+pub fn emit(authority: Option<Pubkey>, event: &Event) {
+    let line = EventLine {
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        authority: authority.map(|authority| authority.to_string()),
+        event,
+    };
+    println!("{}", serde_json::to_string(&line).unwrap());
+}
+
+/// Prints a line of text for people. While events are being written to stdout,
+/// it goes to stderr instead.
+pub fn log(events: bool, message: impl Display) {
+    if events {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+impl Miner {
+    /// Writes an event for this miner's authority, if events were asked for.
+    pub fn emit(&self, event: Event) {
+        if self.events.is_some() {
+            emit(Some(self.signer().pubkey()), &event);
+        }
+    }
+
+    pub fn log(&self, message: impl Display) {
+        log(self.events.is_some(), message);
+    }
+
+    /// Reports something that went wrong, as an `error` event if events were
+    /// asked for.
+    pub fn error(&self, message: impl Display) {
+        match self.events {
+            Some(_) => self.emit(Event::Error {
+                message: message.to_string(),
+            }),
+            None => println!("{}", message),
+        }
+    }
+}
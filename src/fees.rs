@@ -0,0 +1,380 @@
+use std::{fmt, str::FromStr};
+
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::{compute_budget, compute_budget::ComputeBudgetInstruction};
+
+use crate::Miner;
+
+// The most `percentile:<p>` pays per compute unit unless given another ceiling
+const DEFAULT_FEE_CEILING: u64 = 1_000_000;
+
+// The most accounts `getRecentPrioritizationFees` accepts
+const MAX_FEE_ACCOUNTS: usize = 128;
+
+// The price `x<multiplier>` steps multiply when the first attempt paid less,
+// since multiplying a free first attempt would never raise the price
+const MIN_MULTIPLIED_FEE: u64 = 1_000;
+
+/// How the compute unit price of each transaction is chosen: `static` pays
+/// `--priority-fee`, `percentile:<p>[:<ceiling>]` pays that percentile of the
+/// recent prioritization fees of the accounts the transaction uses, up to the
+/// ceiling, and `max:<cap>` pays the highest recent fee, up to the cap.
+#[derive(Clone, Copy, Debug)]
+pub enum FeeStrategy {
+    Static,
+    Percentile { percentile: u8, ceiling: u64 },
+    Max { cap: u64 },
+}
+
+impl FromStr for FeeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let number = |s: &str| {
+            s.parse::<u64>()
+                .map_err(|_| format!("expected a number, got `{}`", s))
+        };
+        match parts.as_slice() {
+            ["static"] => Ok(FeeStrategy::Static),
+            ["percentile", p] | ["percentile", p, _] => {
+                let percentile = number(p)?;
+                if percentile > 100 {
+                    return Err(format!(
+                        "percentile must be at most 100, got {}",
+                        percentile
+                    ));
+                }
+                let ceiling = match parts.get(2) {
+                    Some(ceiling) => number(ceiling)?,
+                    None => DEFAULT_FEE_CEILING,
+                };
+                Ok(FeeStrategy::Percentile {
+                    percentile: percentile as u8,
+                    ceiling,
+                })
+            }
+            ["max", cap] => Ok(FeeStrategy::Max { cap: number(cap)? }),
+            _ => Err(format!(
+                "expected `static`, `percentile:<p>[:<ceiling>]` or `max:<cap>`, got `{}`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FeeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeeStrategy::Static => write!(f, "static"),
+            FeeStrategy::Percentile {
+                percentile,
+                ceiling,
+            } => write!(f, "percentile:{}:{}", percentile, ceiling),
+            FeeStrategy::Max { cap } => write!(f, "max:{}", cap),
+        }
+    }
+}
+
+/// How the compute unit price rises each time a transaction is resent:
+/// `<start>:<step>:<cap>`. The first attempt pays `start`, or whatever
+/// `--fee-strategy` picks if it is `auto`. Each retry multiplies the price by
+/// `x<multiplier>` or adds `+<microlamports>` to it, and no attempt pays more
+/// than `cap`. Multiplied retries start from at least `MIN_MULTIPLIED_FEE`.
+#[derive(Clone, Copy, Debug)]
+pub struct FeeEscalation {
+    pub start: Option<u64>,
+    pub step: FeeStep,
+    pub cap: u64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum FeeStep {
+    Multiply(f64),
+    Add(u64),
+}
+
+impl FeeEscalation {
+    /// The compute unit price of the retry numbered `attempt`, counting the
+    /// first send as zero, for a transaction first priced at `base`.
+    pub fn fee(&self, base: u64, attempt: usize) -> u64 {
+        let base = self.start.unwrap_or(base);
+        let fee = match self.step {
+            FeeStep::Multiply(_) if attempt == 0 => base,
+            FeeStep::Multiply(multiplier) => {
+                let base = base.max(MIN_MULTIPLIED_FEE);
+                (base as f64 * multiplier.powi(attempt as i32)).min(u64::MAX as f64) as u64
+            }
+            FeeStep::Add(step) => base.saturating_add(step.saturating_mul(attempt as u64)),
+        };
+        fee.min(self.cap)
+    }
+}
+
+impl FromStr for FeeEscalation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [start, step, cap] = s.split(':').collect::<Vec<_>>()[..] else {
+            return Err(format!("expected `<start>:<step>:<cap>`, got `{}`", s));
+        };
+        let number = |s: &str| {
+            s.parse::<u64>()
+                .map_err(|_| format!("expected a number, got `{}`", s))
+        };
+        let start = match start {
+            "auto" => None,
+            start => Some(number(start)?),
+        };
+        let step = if let Some(multiplier) = step.strip_prefix('x') {
+            match multiplier.parse::<f64>() {
+                Ok(multiplier) if multiplier >= 1.0 => FeeStep::Multiply(multiplier),
+                _ => {
+                    return Err(format!(
+                        "expected a multiplier of at least 1, got `{}`",
+                        multiplier
+                    ))
+                }
+            }
+        } else if let Some(step) = step.strip_prefix('+') {
+            FeeStep::Add(number(step)?)
+        } else {
+            return Err(format!(
+                "expected a step of `x<multiplier>` or `+<microlamports>`, got `{}`",
+                step
+            ));
+        };
+        Ok(FeeEscalation {
+            start,
+            step,
+            cap: number(cap)?,
+        })
+    }
+}
+
+impl Miner {
+    /// Returns the compute unit price, in microlamports, to pay for a
+    /// transaction made of `ixs`. Falls back to `--priority-fee` if recent fees
+    /// cannot be fetched.
+    pub async fn priority_fee(&self, ixs: &[Instruction]) -> u64 {
+        if let FeeStrategy::Static = self.fee_strategy {
+            return self.priority_fee;
+        }
+
+        // Every program and account the transaction locks
+        let mut accounts: Vec<Pubkey> = vec![];
+        for ix in ixs {
+            let keys = std::iter::once(ix.program_id).chain(ix.accounts.iter().map(|a| a.pubkey));
+            for key in keys {
+                if !accounts.contains(&key) {
+                    accounts.push(key);
+                }
+            }
+        }
+        accounts.truncate(MAX_FEE_ACCOUNTS);
+
+        let mut fees: Vec<u64> = match self
+            .rpc_client
+            .get_recent_prioritization_fees(&accounts)
+            .await
+        {
+            Ok(fees) => fees.iter().map(|fee| fee.prioritization_fee).collect(),
+            Err(err) => {
+                self.error(format!(
+                    "Failed to fetch recent prioritization fees, using --priority-fee: {}",
+                    err
+                ));
+                return self.priority_fee;
+            }
+        };
+        fees.sort_unstable();
+        let fee = match self.fee_strategy {
+            FeeStrategy::Static => self.priority_fee,
+            FeeStrategy::Percentile {
+                percentile,
+                ceiling,
+            } => percentile_fee(&fees, percentile).min(ceiling),
+            FeeStrategy::Max { cap } => fees.last().copied().unwrap_or_default().min(cap),
+        };
+        self.log(format!(
+            "Priority fee: {} microlamports ({} of {} recent slots)",
+            fee,
+            self.fee_strategy,
+            fees.len()
+        ));
+        fee
+    }
+}
+
+/// The fee at `percentile` of sorted `fees`, or zero if there are none.
+fn percentile_fee(fees: &[u64], percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    let rank = (fees.len() * percentile as usize).div_ceil(100);
+    fees[rank.saturating_sub(1).min(fees.len() - 1)]
+}
+
+/// The compute unit price `ixs` set, if any.
+pub fn compute_unit_price(ixs: &[Instruction]) -> Option<u64> {
+    ixs.iter()
+        .filter(|ix| ix.program_id == compute_budget::id())
+        .find_map(|ix| match ix.data.split_first() {
+            Some((3, data)) if data.len() >= 8 => {
+                Some(u64::from_le_bytes(data[..8].try_into().unwrap()))
+            }
+            _ => None,
+        })
+}
+
+/// Returns `ixs` paying `price` per compute unit, replacing the price they set
+/// or adding one in front.
+pub fn with_compute_unit_price(ixs: &[Instruction], price: u64) -> Vec<Instruction> {
+    let price_ix = ComputeBudgetInstruction::set_compute_unit_price(price);
+    let is_price_ix =
+        |ix: &Instruction| ix.program_id == compute_budget::id() && ix.data.first() == Some(&3);
+    match ixs.iter().position(is_price_ix) {
+        Some(index) => {
+            let mut ixs = ixs.to_vec();
+            ixs[index] = price_ix;
+            ixs
+        }
+        None => std::iter::once(price_ix)
+            .chain(ixs.iter().cloned())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_of_recent_fees() {
+        let fees = [10, 20, 30, 40, 50, 60, 70, 80, 90, 100];
+        assert_eq!(percentile_fee(&[], 50), 0);
+        assert_eq!(percentile_fee(&fees, 0), 10);
+        assert_eq!(percentile_fee(&fees, 50), 50);
+        assert_eq!(percentile_fee(&fees, 75), 80);
+        assert_eq!(percentile_fee(&fees, 100), 100);
+        assert_eq!(percentile_fee(&[7], 0), 7);
+        assert_eq!(percentile_fee(&[7], 100), 7);
+    }
+
+    #[test]
+    fn parses_fee_strategies() {
+        assert!(matches!("static".parse(), Ok(FeeStrategy::Static)));
+        assert!(matches!(
+            "percentile:75".parse(),
+            Ok(FeeStrategy::Percentile {
+                percentile: 75,
+                ceiling: DEFAULT_FEE_CEILING
+            })
+        ));
+        assert!(matches!(
+            "percentile:0:5000".parse(),
+            Ok(FeeStrategy::Percentile {
+                percentile: 0,
+                ceiling: 5000
+            })
+        ));
+        assert!(matches!(
+            "percentile:100".parse(),
+            Ok(FeeStrategy::Percentile {
+                percentile: 100,
+                ..
+            })
+        ));
+        assert!(matches!(
+            "max:9000".parse(),
+            Ok(FeeStrategy::Max { cap: 9000 })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_fee_strategies() {
+        for s in [
+            "",
+            "dynamic",
+            "percentile",
+            "percentile:101",
+            "percentile:-1",
+            "percentile:50:",
+            "percentile:50:1000:1",
+            "max",
+            "max:lots",
+        ] {
+            assert!(s.parse::<FeeStrategy>().is_err(), "accepted `{}`", s);
+        }
+    }
+
+    #[test]
+    fn fee_strategies_round_trip() {
+        for s in ["static", "percentile:90:20000", "max:5000"] {
+            assert_eq!(s.parse::<FeeStrategy>().unwrap().to_string(), s);
+        }
+    }
+
+    fn escalation(s: &str) -> FeeEscalation {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn add_step() {
+        let fees = escalation("auto:+500:2000");
+        assert_eq!(fees.fee(100, 0), 100);
+        assert_eq!(fees.fee(100, 1), 600);
+        assert_eq!(fees.fee(100, 3), 1600);
+        assert_eq!(fees.fee(100, 4), 2000);
+        assert_eq!(fees.fee(100, usize::MAX), 2000);
+    }
+
+    #[test]
+    fn multiply_step() {
+        let fees = escalation("auto:x2:100000");
+        assert_eq!(fees.fee(5000, 0), 5000);
+        assert_eq!(fees.fee(5000, 1), 10000);
+        assert_eq!(fees.fee(5000, 3), 40000);
+        assert_eq!(fees.fee(5000, 5), 100000);
+        assert_eq!(fees.fee(5000, 1000), 100000);
+    }
+
+    #[test]
+    fn start_overrides_base_price() {
+        let fees = escalation("2000:+1000:10000");
+        assert_eq!(fees.fee(50, 0), 2000);
+        assert_eq!(fees.fee(50, 2), 4000);
+    }
+
+    #[test]
+    fn multiplied_free_attempt_still_escalates() {
+        let fees = escalation("auto:x2:1000000");
+        assert_eq!(fees.fee(0, 0), 0);
+        assert_eq!(fees.fee(0, 1), 2 * MIN_MULTIPLIED_FEE);
+        assert_eq!(fees.fee(0, 2), 4 * MIN_MULTIPLIED_FEE);
+        assert_eq!(escalation("0:x3:1000000").fee(0, 1), 3 * MIN_MULTIPLIED_FEE);
+    }
+
+    #[test]
+    fn cap_applies_to_the_first_attempt() {
+        assert_eq!(escalation("5000:+100:1000").fee(0, 0), 1000);
+        assert_eq!(escalation("auto:x2:1000").fee(5000, 0), 1000);
+    }
+
+    #[test]
+    fn rejects_invalid_escalations() {
+        for s in [
+            "",
+            "auto:x2",
+            "auto:x2:1000:5",
+            "free:x2:1000",
+            "auto:x0.5:1000",
+            "auto:xabc:1000",
+            "auto:+-1:1000",
+            "auto:2:1000",
+            "auto:x2:lots",
+        ] {
+            assert!(s.parse::<FeeEscalation>().is_err(), "accepted `{}`", s);
+        }
+    }
+}
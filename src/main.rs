@@ -1,33 +1,78 @@
+mod backend;
 mod balance;
+mod bench;
 mod busses;
+mod checkpoint;
 mod claim;
+mod confirm;
+mod coordinator;
 mod cu_limits;
+mod dispatch;
+mod endpoints;
+mod events;
+mod fees;
 #[cfg(feature = "admin")]
 mod initialize;
 mod mine;
+mod mine_pool;
+mod pool;
+mod progress;
+mod protocol;
 mod register;
 mod rewards;
+mod schedule;
 mod send_and_confirm;
+mod session;
+mod shares;
+mod solve;
+mod stats;
 mod treasury;
 #[cfg(feature = "admin")]
 mod update_admin;
 #[cfg(feature = "admin")]
 mod update_difficulty;
 mod utils;
+mod worker;
+mod workers;
 
-use std::sync::Arc;
+use std::{
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc, OnceLock},
+    time::Duration,
+};
 
-use clap::{command, Parser, Subcommand};
+use backend::{backend_compiled, hash_backend, BackendKind, HashBackend};
+use clap::{command, Parser, Subcommand, ValueEnum};
+use events::Event;
+use fees::{FeeEscalation, FeeStrategy};
+use mine::ThreadCount;
+use schedule::Schedule;
+use session::SessionStats;
 use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    keccak::Hash as KeccakHash,
     signature::{read_keypair_file, Keypair},
 };
+use solve::parse_hex_hash;
+use workers::{WorkerOptions, WorkerPool};
 
 struct Miner {
     pub keypair_filepath: Option<String>,
     pub priority_fee: u64,
+    pub fee_strategy: FeeStrategy,
+    pub fee_escalation: Option<FeeEscalation>,
+    pub send_timeout: Duration,
     pub rpc_client: Arc<RpcClient>,
+    pub send_clients: Vec<Arc<RpcClient>>,
+    pub ws_url: String,
+    pub data_dir: PathBuf,
+    pub dry_run: bool,
+    pub events: Option<EventFormat>,
+    pub worker_pool: OnceLock<Arc<WorkerPool>>,
+    pub stats: Arc<SessionStats>,
+    pub shutdown: Arc<AtomicBool>,
 }
 
 #[derive(Parser, Debug)]
@@ -36,10 +81,28 @@ struct Args {
     #[arg(
         long,
         value_name = "NETWORK_URL",
-        help = "Network address of your RPC provider",
+        help = "Network address of your RPC provider. Takes several, comma-separated, to read from the fastest healthy one, re-checked every few minutes and when it fails",
+        value_delimiter = ',',
+        global = true
+    )]
+    rpc: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "NETWORK_URL",
+        help = "Network addresses to send every transaction to at once, comma-separated. Defaults to --rpc",
+        value_delimiter = ',',
         global = true
     )]
-    rpc: Option<String>,
+    send_rpc: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "WEBSOCKET_URL",
+        help = "Pubsub address of your RPC provider, used to confirm transactions. Derived from --rpc by default",
+        global = true
+    )]
+    ws: Option<String>,
 
     #[clap(
         global = true,
@@ -67,6 +130,40 @@ struct Args {
     )]
     priority_fee: u64,
 
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "How to price transactions: `static` pays --priority-fee, `percentile:<p>[:<ceiling>]` pays that percentile of recent fees, `max:<cap>` pays the highest recent fee up to the cap",
+        default_value = "static",
+        global = true
+    )]
+    fee_strategy: FeeStrategy,
+
+    #[arg(
+        long,
+        value_name = "POLICY",
+        help = "Raise the priority fee each time a transaction is resent: `<start>:<step>:<cap>`, where start is a price or `auto` for the --fee-strategy price, step is `x<multiplier>` (multiplying at least 1000) or `+<microlamports>`, and cap is the most any attempt pays",
+        global = true
+    )]
+    fee_escalation: Option<FeeEscalation>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "How long to keep resending a transaction, with a fresh blockhash each time the last one expires, before giving up",
+        default_value = "120",
+        global = true
+    )]
+    send_timeout: u64,
+
+    #[arg(
+        long,
+        value_name = "DIRECTORY",
+        help = "Directory to keep mining state in. Defaults to ~/.config/ore-cli",
+        global = true
+    )]
+    data_dir: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -76,18 +173,39 @@ enum Commands {
     #[command(about = "Fetch the Ore balance of an account")]
     Balance(BalanceArgs),
 
+    #[command(about = "Measure the hash rate of this machine")]
+    Bench(BenchArgs),
+
     #[command(about = "Fetch the distributable rewards of the busses")]
     Busses(BussesArgs),
 
     #[command(about = "Mine Ore using local compute")]
     Mine(MineArgs),
 
+    #[command(about = "Solve a challenge offline and print the solution as JSON")]
+    Solve(SolveArgs),
+
+    #[command(about = "Validate and submit a solution from `ore solve`")]
+    SubmitSolution(SubmitSolutionArgs),
+
+    #[command(about = "Hand out nonce ranges to remote workers and submit their solutions")]
+    Coordinator(CoordinatorArgs),
+
+    #[command(about = "Search nonce ranges handed out by a coordinator")]
+    Worker(WorkerArgs),
+
+    #[command(about = "Run or pay out a mining pool")]
+    Pool(PoolArgs),
+
     #[command(about = "Claim available mining rewards")]
     Claim(ClaimArgs),
 
     #[command(about = "Fetch your balance of unclaimed mining rewards")]
     Rewards(RewardsArgs),
 
+    #[command(about = "Summarize the solutions recorded by `ore mine`")]
+    Stats(StatsArgs),
+
     #[command(about = "Fetch the treasury account and balance")]
     Treasury(TreasuryArgs),
 
@@ -114,9 +232,81 @@ struct BalanceArgs {
     pub address: Option<String>,
 }
 
+#[derive(Parser, Debug)]
+struct BenchArgs {
+    #[arg(
+        long,
+        short,
+        value_name = "THREAD_COUNT",
+        help = "The largest number of threads to measure. Defaults to the number of CPUs.",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    threads: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "How long to measure each thread count for",
+        default_value = "5"
+    )]
+    duration: u64,
+
+    #[arg(
+        long,
+        value_name = "BACKEND",
+        help = "The hashing backend to measure",
+        default_value = "auto"
+    )]
+    backend: BackendKind,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "How to print the results",
+        default_value = "table"
+    )]
+    format: OutputFormat,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
+}
+
 #[derive(Parser, Debug)]
 struct BussesArgs {}
 
+#[derive(Parser, Debug)]
+struct StatsArgs {
+    #[arg(
+        long,
+        value_name = "PERIOD",
+        help = "The period to group solutions by",
+        default_value = "day"
+    )]
+    period: StatsPeriod,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "How to print the summary",
+        default_value = "table"
+    )]
+    format: OutputFormat,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum EventFormat {
+    Jsonl,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum StatsPeriod {
+    Day,
+    Week,
+}
+
 #[derive(Parser, Debug)]
 struct RewardsArgs {
     #[arg(
@@ -133,10 +323,222 @@ struct MineArgs {
         long,
         short,
         value_name = "THREAD_COUNT",
-        help = "The number of threads to dedicate to mining",
+        help = "The number of threads to dedicate to mining, or `auto` to pick the fastest",
         default_value = "1"
     )]
-    threads: u64,
+    threads: ThreadCount,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "How often to re-calibrate the thread count when using `--threads auto`. With --keypairs it is only calibrated once, at startup",
+        default_value = "3600"
+    )]
+    recalibrate_interval: u64,
+
+    #[arg(
+        long,
+        help = "Pin each mining thread to its own CPU core",
+        default_value = "false"
+    )]
+    pin_cores: bool,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        help = "Limit each mining thread to this share of CPU time, so the machine stays usable",
+        value_parser = clap::value_parser!(u8).range(1..=100)
+    )]
+    max_cpu: Option<u8>,
+
+    #[arg(
+        long,
+        value_name = "NICENESS",
+        help = "Lower the scheduling priority of the mining threads (0-19)",
+        value_parser = clap::value_parser!(i32).range(0..=19)
+    )]
+    nice: Option<i32>,
+
+    #[arg(
+        long,
+        value_name = "BACKEND",
+        help = "The hashing backend to search for nonces with",
+        default_value = "auto"
+    )]
+    backend: BackendKind,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "Mine for the pool server at this address instead of on your own proof"
+    )]
+    pool: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Only mine inside the windows listed in this schedule file"
+    )]
+    schedule: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR|LIST",
+        help = "Mine for every keypair in this directory, or in this comma separated list of keypair files, sharing one set of threads",
+        conflicts_with = "pool"
+    )]
+    keypairs: Option<String>,
+
+    #[arg(
+        long,
+        help = "Solve real challenges but only simulate transactions, recording what would have been earned",
+        conflicts_with = "pool"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write a JSON report of the session to this file on exit",
+        conflicts_with = "pool"
+    )]
+    report: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "Write every state transition to stdout in this format instead of drawing a status screen",
+        conflicts_with = "pool"
+    )]
+    events: Option<EventFormat>,
+}
+
+#[derive(Parser, Debug)]
+struct CoordinatorArgs {
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "The address to accept worker connections on",
+        default_value = "0.0.0.0:7878"
+    )]
+    listen: String,
+}
+
+#[derive(Parser, Debug)]
+struct WorkerArgs {
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "The address of the coordinator to connect to"
+    )]
+    connect: String,
+
+    #[arg(
+        long,
+        short,
+        value_name = "THREAD_COUNT",
+        help = "The number of threads to dedicate to mining. Defaults to the number of CPUs.",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    threads: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "BACKEND",
+        help = "The hashing backend to search for nonces with",
+        default_value = "auto"
+    )]
+    backend: BackendKind,
+}
+
+#[derive(Parser, Debug)]
+struct PoolArgs {
+    #[command(subcommand)]
+    command: PoolCommands,
+}
+
+#[derive(Subcommand, Debug)]
+enum PoolCommands {
+    #[command(about = "Mine as a pool authority, crediting members for their shares")]
+    Serve(PoolServeArgs),
+
+    #[command(about = "Claim pool rewards and pay members in proportion to their shares")]
+    Payout(PoolPayoutArgs),
+}
+
+#[derive(Parser, Debug)]
+struct PoolServeArgs {
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "The address to accept member connections on",
+        default_value = "0.0.0.0:7879"
+    )]
+    listen: String,
+
+    #[arg(
+        long,
+        value_name = "HEX",
+        help = "The largest hash credited as a share",
+        default_value = "000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        value_parser = parse_hex_hash
+    )]
+    share_difficulty: KeccakHash,
+}
+
+#[derive(Parser, Debug)]
+struct PoolPayoutArgs {}
+
+#[derive(Parser, Debug)]
+struct SolveArgs {
+    #[arg(
+        long,
+        value_name = "HEX",
+        help = "The proof's current challenge hash",
+        value_parser = parse_hex_hash
+    )]
+    challenge: KeccakHash,
+
+    #[arg(
+        long,
+        value_name = "PUBKEY",
+        help = "The authority of the proof being solved"
+    )]
+    authority: Pubkey,
+
+    #[arg(
+        long,
+        value_name = "HEX",
+        help = "The treasury's current difficulty",
+        value_parser = parse_hex_hash
+    )]
+    difficulty: KeccakHash,
+
+    #[arg(
+        long,
+        short,
+        value_name = "THREAD_COUNT",
+        help = "The number of threads to dedicate to solving. Defaults to the number of CPUs.",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    threads: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "BACKEND",
+        help = "The hashing backend to search for nonces with",
+        default_value = "auto"
+    )]
+    backend: BackendKind,
+}
+
+#[derive(Parser, Debug)]
+struct SubmitSolutionArgs {
+    #[arg(
+        value_name = "PATH",
+        help = "File containing the JSON printed by `ore solve`. Reads stdin if omitted."
+    )]
+    path: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -190,14 +592,59 @@ async fn main() {
     };
 
     // Initialize miner.
-    let cluster = args.rpc.unwrap_or(cli_config.json_rpc_url);
+    let (dry_run, events) = match &args.command {
+        Commands::Mine(args) => (args.dry_run, args.events),
+        _ => (false, None),
+    };
+    let read_urls = if args.rpc.is_empty() {
+        vec![cli_config.json_rpc_url.clone()]
+    } else {
+        args.rpc.clone()
+    };
+    let (rpc_client, cluster) = endpoints::read_client(read_urls.clone(), events.is_some()).await;
+    // The config file's websocket URL only applies to its own RPC URL
+    let ws_url = args.ws.unwrap_or_else(|| {
+        if args.rpc.is_empty() && !cli_config.websocket_url.is_empty() {
+            cli_config.websocket_url.clone()
+        } else {
+            solana_cli_config::Config::compute_websocket_url(&cluster)
+        }
+    });
+    let send_urls = if args.send_rpc.is_empty() {
+        read_urls
+    } else {
+        args.send_rpc
+    };
+    let send_clients = send_urls
+        .into_iter()
+        .map(|url| {
+            Arc::new(RpcClient::new_with_commitment(
+                url,
+                CommitmentConfig::finalized(),
+            ))
+        })
+        .collect();
     let default_keypair = args.keypair.unwrap_or(cli_config.keypair_path);
-    let rpc_client = RpcClient::new_with_commitment(cluster, CommitmentConfig::finalized());
+
+    let data_dir = args.data_dir.map(PathBuf::from).unwrap_or_else(|| {
+        dirs_next::home_dir()
+            .unwrap_or_default()
+            .join(".config")
+            .join("ore-cli")
+    });
 
     let miner = Arc::new(Miner::new(
         Arc::new(rpc_client),
+        send_clients,
+        ws_url,
         args.priority_fee,
+        args.fee_strategy,
+        args.fee_escalation,
+        Duration::from_secs(args.send_timeout),
         Some(default_keypair),
+        data_dir,
+        dry_run,
+        events,
     ));
 
     // Execute user command.
@@ -205,17 +652,98 @@ async fn main() {
         Commands::Balance(args) => {
             miner.balance(args.address).await;
         }
+        Commands::Bench(args) => {
+            let threads = args.threads.unwrap_or_else(bench::available_threads);
+            miner.bench(
+                threads,
+                args.duration,
+                load_backend(args.backend),
+                args.format,
+            );
+        }
         Commands::Busses(_) => {
             miner.busses().await;
         }
         Commands::Rewards(args) => {
             miner.rewards(args.address).await;
         }
+        Commands::Stats(args) => {
+            miner.stats(args.period, args.format);
+        }
         Commands::Treasury(_) => {
             miner.treasury().await;
         }
         Commands::Mine(args) => {
-            miner.mine(args.threads).await;
+            let options = WorkerOptions {
+                pin_cores: args.pin_cores,
+                max_cpu: args.max_cpu,
+                nice: args.nice,
+            };
+            let schedule = args.schedule.as_deref().map(load_schedule);
+            if let Some(pool) = args.pool {
+                miner
+                    .mine_pool(
+                        pool,
+                        args.threads,
+                        Duration::from_secs(args.recalibrate_interval),
+                        load_backend(args.backend),
+                        options,
+                        schedule,
+                    )
+                    .await;
+                return;
+            }
+            let report = miner
+                .mine(
+                    args.keypairs.as_deref().map(load_keypair_paths),
+                    args.threads,
+                    Duration::from_secs(args.recalibrate_interval),
+                    load_backend(args.backend),
+                    options,
+                    schedule,
+                )
+                .await;
+            if let Some(path) = args.report {
+                if let Err(err) = report.save(&path) {
+                    eprintln!("error: Failed to write {}: {}", path.display(), err);
+                }
+            }
+            match args.events {
+                Some(EventFormat::Jsonl) => events::emit(None, &Event::SessionEnded(report)),
+                None => report.print(),
+            }
+        }
+        Commands::Solve(args) => {
+            let threads = args.threads.unwrap_or_else(bench::available_threads);
+            miner
+                .solve(
+                    args.challenge,
+                    args.authority,
+                    args.difficulty,
+                    threads,
+                    load_backend(args.backend),
+                )
+                .await;
+        }
+        Commands::SubmitSolution(args) => {
+            miner.submit_solution(args.path).await;
+        }
+        Commands::Coordinator(args) => {
+            miner.coordinator(args.listen).await;
+        }
+        Commands::Pool(args) => match args.command {
+            PoolCommands::Serve(args) => {
+                miner.pool_serve(args.listen, args.share_difficulty).await;
+            }
+            PoolCommands::Payout(_) => {
+                miner.pool_payout().await;
+            }
+        },
+        Commands::Worker(args) => {
+            let threads = args.threads.unwrap_or_else(bench::available_threads);
+            miner
+                .worker(args.connect, threads, load_backend(args.backend))
+                .await;
         }
         Commands::Claim(args) => {
             miner.claim(args.beneficiary, args.amount).await;
@@ -235,12 +763,118 @@ async fn main() {
     }
 }
 
+fn load_backend(kind: BackendKind) -> Arc<dyn HashBackend> {
+    hash_backend(kind).unwrap_or_else(|| {
+        if backend_compiled(kind) {
+            eprintln!(
+                "error: The {:?} hash backend is not supported by this CPU",
+                kind
+            );
+        } else {
+            eprintln!(
+                "error: The {:?} hash backend is not compiled into this build",
+                kind
+            );
+        }
+        std::process::exit(1);
+    })
+}
+
+/// Resolves `--keypairs` to keypair files: every `.json` file in a directory, or
+/// a comma separated list of paths.
+fn load_keypair_paths(keypairs: &str) -> Vec<String> {
+    let path = Path::new(keypairs);
+    let paths: Vec<String> = if path.is_dir() {
+        let entries = std::fs::read_dir(path).unwrap_or_else(|err| {
+            eprintln!("error: Failed to read {}: {}", path.display(), err);
+            std::process::exit(1);
+        });
+        let mut paths: Vec<String> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        paths.sort();
+        paths
+    } else {
+        keypairs
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    if paths.is_empty() {
+        eprintln!("error: No keypairs found in {}", keypairs);
+        std::process::exit(1);
+    }
+    for path in &paths {
+        if let Err(err) = read_keypair_file(path) {
+            eprintln!("error: Failed to read keypair {}: {}", path, err);
+            std::process::exit(1);
+        }
+    }
+    paths
+}
+
+fn load_schedule(path: &Path) -> Schedule {
+    Schedule::load(path).unwrap_or_else(|err| {
+        eprintln!("error: Failed to read schedule {}: {}", path.display(), err);
+        std::process::exit(1);
+    })
+}
+
 impl Miner {
-    pub fn new(rpc_client: Arc<RpcClient>, priority_fee: u64, keypair_filepath: Option<String>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        send_clients: Vec<Arc<RpcClient>>,
+        ws_url: String,
+        priority_fee: u64,
+        fee_strategy: FeeStrategy,
+        fee_escalation: Option<FeeEscalation>,
+        send_timeout: Duration,
+        keypair_filepath: Option<String>,
+        data_dir: PathBuf,
+        dry_run: bool,
+        events: Option<EventFormat>,
+    ) -> Self {
         Self {
             rpc_client,
+            send_clients,
+            ws_url,
             keypair_filepath,
             priority_fee,
+            fee_strategy,
+            fee_escalation,
+            send_timeout,
+            data_dir,
+            dry_run,
+            events,
+            worker_pool: OnceLock::new(),
+            stats: Arc::default(),
+            shutdown: Arc::default(),
+        }
+    }
+
+    /// Returns a miner for another keypair that shares this miner's RPC client,
+    /// worker pool and shutdown flag, but keeps its own session statistics.
+    pub fn with_keypair(&self, keypair_filepath: String) -> Self {
+        Self {
+            rpc_client: self.rpc_client.clone(),
+            send_clients: self.send_clients.clone(),
+            ws_url: self.ws_url.clone(),
+            keypair_filepath: Some(keypair_filepath),
+            priority_fee: self.priority_fee,
+            fee_strategy: self.fee_strategy,
+            fee_escalation: self.fee_escalation,
+            send_timeout: self.send_timeout,
+            data_dir: self.data_dir.clone(),
+            dry_run: self.dry_run,
+            events: self.events,
+            worker_pool: self.worker_pool.clone(),
+            stats: Arc::default(),
+            shutdown: self.shutdown.clone(),
         }
     }
 
@@ -1,131 +1,627 @@
 use std::{
     io::{stdout, Write},
-    sync::{atomic::AtomicBool, Arc, Mutex},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
-use ore::{self, state::Bus, BUS_ADDRESSES, BUS_COUNT, EPOCH_DURATION};
-use rand::Rng;
+use ore::{
+    self,
+    state::{Bus, Proof},
+    utils::AccountDeserialize,
+    BUS_ADDRESSES, EPOCH_DURATION,
+};
+use rand::{seq::SliceRandom, Rng};
+use solana_client::{client_error::Result as ClientResult, nonblocking::rpc_client::RpcClient};
 use solana_program::{keccak::HASH_BYTES, program_memory::sol_memcmp, pubkey::Pubkey};
 use solana_sdk::{
+    commitment_config::CommitmentConfig,
     compute_budget::ComputeBudgetInstruction,
     keccak::{hashv, Hash as KeccakHash},
     signature::Signer,
 };
+use spl_token::amount_to_ui_amount;
 
 use crate::{
+    backend::HashBackend,
+    bench::{available_threads, calibrate_threads},
+    checkpoint::{save_checkpoint, save_checkpoints},
     cu_limits::{CU_LIMIT_MINE, CU_LIMIT_RESET},
-    utils::{get_clock_account, get_proof, get_treasury},
+    dispatch::CHALLENGE_POLL_INTERVAL,
+    events::{log, Event},
+    progress::{expected_hashes, format_count, format_duration, report_progress},
+    schedule::{enforce_schedule, Schedule},
+    send_and_confirm::TxReceipt,
+    session::{shutdown_signal, SessionReport, SessionStats},
+    stats::{SolveRecord, SubmitResult},
+    utils::{get_clock_account, get_proof, get_treasury, proof_pubkey},
+    workers::{nonce_ranges, WorkerOptions, WorkerPool},
     Miner,
 };
 
 // Odds of being selected to submit a reset tx
 const RESET_ODDS: u64 = 20;
 
+// How often per-authority statistics are printed when mining for several authorities
+const AUTHORITY_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+// How often a miner holding a solution checks whether it was asked to stop
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// How long to wait for pending transactions to confirm after being asked to stop
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The number of threads to mine with, or `auto` to calibrate it at startup.
+#[derive(Clone, Copy, Debug)]
+pub enum ThreadCount {
+    Auto,
+    Fixed(u64),
+}
+
+impl FromStr for ThreadCount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(ThreadCount::Auto);
+        }
+        match s.parse::<u64>() {
+            Ok(n) if n > 0 => Ok(ThreadCount::Fixed(n)),
+            _ => Err(format!("expected a positive number or `auto`, got `{}`", s)),
+        }
+    }
+}
+
+/// Picks the thread count for each search, re-calibrating `--threads auto`
+/// every `recalibrate_interval`. Shared by every authority mined from this process.
+pub struct ThreadTuner {
+    threads: ThreadCount,
+    recalibrate_interval: Duration,
+    calibration: tokio::sync::Mutex<Option<(Instant, u64)>>,
+    events: bool,
+}
+
+impl ThreadTuner {
+    pub fn new(threads: ThreadCount, recalibrate_interval: Duration, events: bool) -> Self {
+        Self {
+            threads,
+            recalibrate_interval,
+            calibration: tokio::sync::Mutex::new(None),
+            events,
+        }
+    }
+
+    pub async fn thread_count(&self, pool: &Arc<WorkerPool>) -> u64 {
+        let ThreadCount::Auto = self.threads else {
+            return pool.size();
+        };
+        let mut calibration = self.calibration.lock().await;
+        match *calibration {
+            Some((at, threads)) if at.elapsed().lt(&self.recalibrate_interval) => threads,
+            _ => {
+                // Calibrating a suspended pool would measure nothing
+                while pool.is_paused() {
+                    tokio::time::sleep(CHALLENGE_POLL_INTERVAL).await;
+                }
+                log(self.events, "Calibrating thread count...");
+                let threads = tokio::task::spawn_blocking({
+                    let pool = pool.clone();
+                    move || calibrate_threads(&pool)
+                })
+                .await
+                .expect("Calibration thread panicked");
+                *calibration = Some((Instant::now(), threads));
+                threads
+            }
+        }
+    }
+}
+
+/// The outcome of submitting a solved hash.
+pub struct Submission {
+    pub result: SubmitResult,
+    pub bus: Option<u64>,
+
+    /// Lamports paid in fees by the mine transaction
+    pub fee: u64,
+    pub compute_units: Option<u64>,
+}
+
 impl Miner {
-    pub async fn mine(&self, threads: u64) {
+    /// Mines with this miner's keypair, or with each of `keypairs` if given. All
+    /// authorities share one worker pool, which splits its time between their
+    /// searches evenly. Mines until SIGINT or SIGTERM, then stops hashing, waits
+    /// for pending transactions to confirm, and returns a report of the session.
+    pub async fn mine(
+        &self,
+        keypairs: Option<Vec<String>>,
+        threads: ThreadCount,
+        recalibrate_interval: Duration,
+        backend: Arc<dyn HashBackend>,
+        options: WorkerOptions,
+        schedule: Option<Schedule>,
+    ) -> SessionReport {
+        let started = Instant::now();
+        let pool_size = match threads {
+            ThreadCount::Fixed(n) => n,
+            ThreadCount::Auto => available_threads(),
+        };
+        let pool = self.worker_pool(&backend, pool_size, options);
+
+        // Only hash inside the allowed mining windows
+        if let Some(schedule) = schedule {
+            tokio::spawn(enforce_schedule(
+                Arc::new(schedule),
+                pool.clone(),
+                self.events.is_some(),
+            ));
+        }
+
+        let others: Vec<Miner> = keypairs
+            .unwrap_or_default()
+            .into_iter()
+            .map(|keypair| self.with_keypair(keypair))
+            .collect();
+        let multi = !others.is_empty();
+        let miners: Vec<&Miner> = if multi {
+            others.iter().collect()
+        } else {
+            vec![self]
+        };
+
+        // Calibrating shares the workers with every queued search and holds up
+        // the other authorities, so with several it is only done once, up front
+        let tuner = ThreadTuner::new(
+            threads,
+            if multi {
+                Duration::MAX
+            } else {
+                recalibrate_interval
+            },
+            self.events.is_some(),
+        );
+        if multi {
+            tuner.thread_count(&pool).await;
+        }
+
+        let authorities: Vec<(Pubkey, Arc<SessionStats>)> = miners
+            .iter()
+            .map(|miner| (miner.signer().pubkey(), miner.stats.clone()))
+            .collect();
+        let reporter = multi.then(|| {
+            self.log(format!(
+                "Mining for {} authorities ({} backend)",
+                miners.len(),
+                pool.backend().name()
+            ));
+            tokio::spawn(report_authorities(
+                authorities.clone(),
+                self.events.is_some(),
+            ))
+        });
+
+        let mining = futures::future::join_all(
+            miners
+                .iter()
+                .map(|miner| miner.mine_authority(&pool, &tuner, multi)),
+        );
+        tokio::pin!(mining);
+        tokio::select! {
+            _ = &mut mining => {}
+            _ = shutdown_signal() => {
+                self.log(format!(
+                    "\n\nShutting down, waiting up to {}s for pending transactions (press Ctrl-C again to quit now)...",
+                    SHUTDOWN_TIMEOUT.as_secs()
+                ));
+                self.shutdown.store(true, Ordering::Relaxed);
+                pool.stop();
+                tokio::select! {
+                    result = tokio::time::timeout(SHUTDOWN_TIMEOUT, &mut mining) => {
+                        if result.is_err() {
+                            self.log("Gave up waiting for pending transactions");
+                        }
+                    }
+                    _ = shutdown_signal() => {}
+                }
+            }
+        }
+        if let Some(reporter) = reporter {
+            reporter.abort();
+        }
+        SessionReport::new(&authorities, started.elapsed(), self.dry_run)
+    }
+
+    /// The mining loop for one authority. With `multi`, several authorities are
+    /// being mined at once, so output is prefixed with the authority instead of
+    /// redrawing the screen. The screen is not drawn when writing events either.
+    async fn mine_authority(&self, pool: &Arc<WorkerPool>, tuner: &ThreadTuner, multi: bool) {
         // Register, if needed.
+        let stats = &self.stats;
         let signer = self.signer();
         self.register().await;
         let mut stdout = stdout();
-        let mut rng = rand::thread_rng();
+        let label = short_pubkey(signer.pubkey());
+        let interactive = !multi && self.events.is_none();
+        let mut restart_reason: Option<&str> = None;
 
         // Start mining loop
         loop {
+            if self.is_shutting_down() {
+                return;
+            }
+
+            // Pick the fastest thread count, if tuning automatically
+            let thread_count = tuner.thread_count(pool).await;
+
             // Fetch account state
-            let balance = self.get_ore_display_balance().await;
             let treasury = get_treasury(&self.rpc_client).await;
-            let proof = get_proof(&self.rpc_client, signer.pubkey()).await;
-            let rewards =
-                (proof.claimable_rewards as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
-            let reward_rate =
-                (treasury.reward_rate as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
-            stdout.write_all(b"\x1b[2J\x1b[3J\x1b[H").ok();
-            println!("Balance: {} ORE", balance);
-            println!("Claimable: {} ORE", rewards);
-            println!("Reward rate: {} ORE", reward_rate);
-
-            // Escape sequence that clears the screen and the scrollback buffer
-            println!("\nMining for a valid hash...");
-            let (next_hash, nonce) =
-                self.find_next_hash_par(proof.hash.into(), treasury.difficulty.into(), threads);
+            let proof = self.mining_proof(signer.pubkey()).await;
+            let difficulty: KeccakHash = treasury.difficulty.into();
+            self.emit(Event::StateFetched {
+                challenge: KeccakHash::from(proof.hash).to_string(),
+                difficulty: difficulty.to_string(),
+                reward_rate: treasury.reward_rate,
+                claimable_rewards: proof.claimable_rewards,
+            });
+            if !interactive {
+                self.log(format!(
+                    "[{}] Mining challenge {} ({} threads)",
+                    label,
+                    KeccakHash::from(proof.hash),
+                    thread_count
+                ));
+            } else {
+                let balance = self.get_ore_display_balance().await;
+                let rewards =
+                    (proof.claimable_rewards as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
+                let reward_rate =
+                    (treasury.reward_rate as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
+                // Escape sequence that clears the screen and the scrollback buffer
+                stdout.write_all(b"\x1b[2J\x1b[3J\x1b[H").ok();
+                if self.dry_run {
+                    println!("Dry run: transactions are simulated, never sent");
+                }
+                println!("Balance: {} ORE", balance);
+                println!("Claimable: {} ORE", rewards);
+                println!("Reward rate: {} ORE", reward_rate);
+                println!("Threads: {}", thread_count);
+                println!(
+                    "Expected hashes: {}",
+                    format_count(expected_hashes(difficulty))
+                );
+                println!(
+                    "Session: {} solutions, {} landed, {} ORE {}",
+                    stats.solutions.load(Ordering::Relaxed),
+                    stats.landed.load(Ordering::Relaxed),
+                    amount_to_ui_amount(stats.earned.load(Ordering::Relaxed), ore::TOKEN_DECIMALS),
+                    if self.dry_run {
+                        "would have been earned"
+                    } else {
+                        "earned"
+                    },
+                );
+                if let Some(reason) = restart_reason.take() {
+                    println!("\n{}", reason);
+                }
+                println!(
+                    "\nMining for a valid hash ({} backend)...",
+                    pool.backend().name()
+                );
+            }
+
+            // Every hash counts towards the session, even if the search is cut short
+            let hashes = stats.hashes.clone();
+            let search_hashes = hashes.load(Ordering::Relaxed);
+            let search_started = Instant::now();
+            let Some((next_hash, nonce)) = self
+                .find_next_hash_par(
+                    pool,
+                    proof.hash.into(),
+                    difficulty,
+                    thread_count,
+                    hashes.clone(),
+                    interactive,
+                )
+                .await
+            else {
+                if self.is_shutting_down() {
+                    return;
+                }
+                self.emit(Event::ChallengeChanged {
+                    challenge: KeccakHash::from(proof.hash).to_string(),
+                });
+                if !interactive {
+                    self.log(format!(
+                        "[{}] Challenge changed on chain, restarting search",
+                        label
+                    ));
+                } else {
+                    restart_reason = Some("Challenge changed on chain, restarting search...");
+                }
+                continue;
+            };
+            let solve_time = search_started.elapsed();
+            let solve_hashes = hashes.load(Ordering::Relaxed) - search_hashes;
 
             // Submit mine tx.
-            // Use busses randomly so on each epoch, transactions don't pile on the same busses
-            println!("\n\nSubmitting hash for validation...");
-            'submit: loop {
-                // Double check we're submitting for the right challenge
-                let proof_ = get_proof(&self.rpc_client, signer.pubkey()).await;
-                if !self.validate_hash(
+            self.emit(Event::SolutionFound {
+                hash: next_hash.to_string(),
+                nonce,
+                hashes: solve_hashes,
+                secs: solve_time.as_secs_f64(),
+            });
+            if !interactive {
+                self.log(format!(
+                    "[{}] Found solution {}, submitting",
+                    label, next_hash
+                ));
+            } else {
+                println!("\n\nSubmitting hash for validation...");
+            }
+            stats.solutions.fetch_add(1, Ordering::Relaxed);
+            let found_at = chrono::Utc::now().timestamp();
+            let rewards_before = self.claimable_rewards(signer.pubkey()).await;
+            let submission = self.land_hash(next_hash, nonce, difficulty).await;
+            // A solution cut short by shutdown is submitted again on the next run
+            if submission.result == SubmitResult::Landed || !self.is_shutting_down() {
+                self.clear_solution(signer.pubkey());
+            }
+            if submission.result == SubmitResult::Landed {
+                stats.landed.fetch_add(1, Ordering::Relaxed);
+                stats
+                    .earned
+                    .fetch_add(treasury.reward_rate, Ordering::Relaxed);
+            }
+
+            // Keep real solutions in the stats store
+            if !self.dry_run {
+                let reward = match (submission.result, rewards_before) {
+                    (SubmitResult::Landed, Some(before)) => self
+                        .claimable_rewards(signer.pubkey())
+                        .await
+                        .map_or(0, |after| after.saturating_sub(before)),
+                    _ => 0,
+                };
+                self.record_solve(&SolveRecord {
+                    time: found_at,
+                    authority: signer.pubkey().to_string(),
+                    challenge: KeccakHash::from(proof.hash).to_string(),
+                    nonce,
+                    hashes: solve_hashes,
+                    solve_secs: solve_time.as_secs_f64(),
+                    submit_secs: search_started
+                        .elapsed()
+                        .saturating_sub(solve_time)
+                        .as_secs_f64(),
+                    bus: submission.bus,
+                    fee: submission.fee,
+                    compute_units: submission.compute_units,
+                    result: submission.result,
+                    reward,
+                });
+            }
+        }
+    }
+
+    /// Returns true once mining has been asked to stop.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.load(Ordering::Relaxed)
+    }
+
+    /// Reads the proof's claimable rewards at confirmed commitment, so a
+    /// transaction that just landed is already counted.
+    async fn claimable_rewards(&self, authority: Pubkey) -> Option<u64> {
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(&proof_pubkey(authority), CommitmentConfig::confirmed())
+            .await
+            .ok()?
+            .value?;
+        Proof::try_from_bytes(&account.data)
+            .ok()
+            .map(|proof| proof.claimable_rewards)
+    }
+
+    /// Fetches the authority's proof. In a dry run, an authority that is not
+    /// registered gets the proof registration would have created.
+    async fn mining_proof(&self, authority: Pubkey) -> Proof {
+        if self.dry_run {
+            let data = self
+                .rpc_client
+                .get_account_data(&proof_pubkey(authority))
+                .await;
+            return match data {
+                Ok(data) => *Proof::try_from_bytes(&data).expect("Failed to parse miner account"),
+                Err(_) => Proof {
+                    authority,
+                    claimable_rewards: 0,
+                    hash: hashv(&[authority.as_ref()]).into(),
+                    total_hashes: 0,
+                    total_rewards: 0,
+                },
+            };
+        }
+        get_proof(&self.rpc_client, authority).await
+    }
+
+    /// Submits a solved hash until it lands or the challenge moves on, first
+    /// sending an epoch reset if one is due. If every bus has run out of rewards,
+    /// the solution is held until the epoch ends and then submitted after a
+    /// reset, which we send ourselves if nobody else has.
+    pub async fn land_hash(
+        &self,
+        next_hash: KeccakHash,
+        nonce: u64,
+        difficulty: KeccakHash,
+    ) -> Submission {
+        let signer = self.signer();
+        let mut submission = Submission {
+            result: SubmitResult::Failed,
+            bus: None,
+            fee: 0,
+            compute_units: None,
+        };
+        let mut force_reset = false;
+        loop {
+            // Let a transaction that is already in flight finish, but send no new ones
+            if self.is_shutting_down() {
+                return submission;
+            }
+
+            // Double check we're submitting for the right challenge. A dry run never
+            // changes the challenge, so there is nothing to check.
+            let proof_ = self.mining_proof(signer.pubkey()).await;
+            if !self.dry_run
+                && !self.validate_hash(
                     next_hash,
                     proof_.hash.into(),
                     signer.pubkey(),
                     nonce,
-                    treasury.difficulty.into(),
-                ) {
-                    println!("Hash already validated! An earlier transaction must have landed.");
-                    break 'submit;
-                }
+                    difficulty,
+                )
+            {
+                self.error("Hash already validated! An earlier transaction must have landed.");
+                submission.result = SubmitResult::Stale;
+                return submission;
+            }
 
-                // Reset epoch, if needed
-                let treasury = get_treasury(&self.rpc_client).await;
-                let clock = get_clock_account(&self.rpc_client).await;
-                let threshold = treasury.last_reset_at.saturating_add(EPOCH_DURATION);
-                if clock.unix_timestamp.ge(&threshold) {
-                    // There are a lot of miners right now, so randomly select into submitting tx
-                    if rng.gen_range(0..RESET_ODDS).eq(&0) {
-                        println!("Sending epoch reset transaction...");
-                        let cu_limit_ix =
-                            ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_RESET);
-                        let cu_price_ix =
-                            ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee);
-                        let reset_ix = ore::instruction::reset(signer.pubkey());
-                        self.send_and_confirm(&[cu_limit_ix, cu_price_ix, reset_ix], false, true)
-                            .await
-                            .ok();
+            // Reset epoch, if needed
+            let treasury = get_treasury(&self.rpc_client).await;
+            let clock = get_clock_account(&self.rpc_client).await;
+            let threshold = treasury.last_reset_at.saturating_add(EPOCH_DURATION);
+            if clock.unix_timestamp.ge(&threshold) {
+                // There are a lot of miners right now, so randomly select into submitting tx,
+                // unless we are waiting on the reset to land a held solution
+                if force_reset || rand::thread_rng().gen_range(0..RESET_ODDS).eq(&0) {
+                    self.log("Sending epoch reset transaction...");
+                    let reset_ix = ore::instruction::reset(signer.pubkey());
+                    let cu_limit_ix =
+                        ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_RESET);
+                    let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+                        self.priority_fee(std::slice::from_ref(&reset_ix)).await,
+                    );
+                    if self
+                        .send_and_confirm(
+                            &[cu_limit_ix, cu_price_ix, reset_ix],
+                            false,
+                            !force_reset,
+                        )
+                        .await
+                        .is_ok()
+                    {
+                        self.emit(Event::ResetSent);
                     }
                 }
+            }
 
-                // Submit request.
-                let bus = self.find_bus_id(treasury.reward_rate).await;
-                let bus_rewards = (bus.rewards as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
-                println!("Sending on bus {} ({} ORE)", bus.id, bus_rewards);
-                let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_MINE);
-                let cu_price_ix =
-                    ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee);
-                let ix_mine = ore::instruction::mine(
-                    signer.pubkey(),
-                    BUS_ADDRESSES[bus.id as usize],
-                    next_hash.into(),
-                    nonce,
-                );
-                match self
-                    .send_and_confirm(&[cu_limit_ix, cu_price_ix, ix_mine], false, false)
-                    .await
-                {
-                    Ok(sig) => {
-                        println!("Success: {}", sig);
-                        break;
-                    }
-                    Err(_err) => {
-                        // TODO
+            // Submit request, or hold the solution until the busses are refilled
+            let Some(bus) = self.find_bus_id(treasury.reward_rate).await else {
+                let wait = threshold.saturating_sub(clock.unix_timestamp).max(0) as u64;
+                if wait > 0 {
+                    self.log(format!(
+                        "All busses are exhausted, holding solution until the epoch resets in {}",
+                        format_duration(Duration::from_secs(wait))
+                    ));
+                    self.emit(Event::BussesExhausted {
+                        reset_at: threshold,
+                    });
+                    self.idle(Duration::from_secs(wait)).await;
+                } else if force_reset {
+                    // Our reset may not have landed, or the busses not been read since
+                    self.idle(CHALLENGE_POLL_INTERVAL).await;
+                }
+                force_reset = true;
+                continue;
+            };
+            submission.bus = Some(bus.id);
+            match self.submit_hash(next_hash, nonce, &bus).await {
+                Ok(receipt) => {
+                    if self.dry_run {
+                        self.log("Mine transaction simulated successfully");
+                    } else {
+                        self.log(format!("Success: {}", receipt.signature));
                     }
+                    submission.result = SubmitResult::Landed;
+                    submission.fee = receipt.fee;
+                    submission.compute_units = receipt.compute_units;
+                    return submission;
+                }
+                Err(err) if self.dry_run => {
+                    self.error(format!("Mine transaction would fail: {}", err));
+                    return submission;
+                }
+                Err(_err) => {
+                    // TODO
                 }
             }
         }
     }
 
-    async fn find_bus_id(&self, reward_rate: u64) -> Bus {
-        let mut rng = rand::thread_rng();
+    /// Sends a mine transaction for a solved hash on `bus`.
+    pub async fn submit_hash(
+        &self,
+        next_hash: KeccakHash,
+        nonce: u64,
+        bus: &Bus,
+    ) -> ClientResult<TxReceipt> {
+        let signer = self.signer();
+        let bus_rewards = (bus.rewards as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
+        self.log(format!("Sending on bus {} ({} ORE)", bus.id, bus_rewards));
+        self.emit(Event::BusChosen {
+            bus: bus.id,
+            rewards: bus.rewards,
+        });
+        let ix_mine = ore::instruction::mine(
+            signer.pubkey(),
+            BUS_ADDRESSES[bus.id as usize],
+            next_hash.into(),
+            nonce,
+        );
+        let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_MINE);
+        let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+            self.priority_fee(std::slice::from_ref(&ix_mine)).await,
+        );
+        self.send_and_confirm_receipt(&[cu_limit_ix, cu_price_ix, ix_mine], false, false)
+            .await
+    }
+
+    /// Picks a random bus that still has enough rewards left for a solution, or
+    /// returns `None` if none does until the next epoch. Using busses randomly
+    /// keeps transactions from piling on the same busses each epoch.
+    pub async fn find_bus_id(&self, reward_rate: u64) -> Option<Bus> {
+        let threshold = reward_rate.saturating_mul(20);
         loop {
-            let bus_id = rng.gen_range(0..BUS_COUNT);
-            if let Ok(bus) = self.get_bus(bus_id).await {
-                if bus.rewards.gt(&reward_rate.saturating_mul(20)) {
-                    return bus;
+            match self.get_busses().await {
+                Ok(busses) => {
+                    let busses: Vec<Bus> = busses
+                        .into_iter()
+                        .filter(|bus| bus.rewards.gt(&threshold))
+                        .collect();
+                    return busses.choose(&mut rand::thread_rng()).copied();
+                }
+                Err(err) => {
+                    self.error(format!("Failed to fetch busses: {}", err));
+                    self.idle(CHALLENGE_POLL_INTERVAL).await;
                 }
             }
         }
     }
 
+    /// Sleeps for `duration`, waking early if mining is asked to stop.
+    async fn idle(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        while !self.is_shutting_down() {
+            let left = until.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return;
+            }
+            tokio::time::sleep(left.min(IDLE_POLL_INTERVAL)).await;
+        }
+    }
+
     fn _find_next_hash(&self, hash: KeccakHash, difficulty: KeccakHash) -> (KeccakHash, u64) {
         let signer = self.signer();
         let mut next_hash: KeccakHash;
@@ -139,76 +635,96 @@ impl Miner {
             if next_hash.le(&difficulty) {
                 break;
             } else {
-                println!("Invalid hash: {} Nonce: {:?}", next_hash.to_string(), nonce);
+                println!("Invalid hash: {} Nonce: {:?}", next_hash, nonce);
             }
             nonce += 1;
         }
         (next_hash, nonce)
     }
 
-    fn find_next_hash_par(
+    /// Searches for a solution to `hash`, or returns `None` if the proof's
+    /// challenge changes on chain before one is found. Hashes are counted into
+    /// `hashes`, and a progress line is drawn if `show_progress` is set.
+    async fn find_next_hash_par(
         &self,
+        pool: &Arc<WorkerPool>,
         hash: KeccakHash,
         difficulty: KeccakHash,
         threads: u64,
-    ) -> (KeccakHash, u64) {
-        let found_solution = Arc::new(AtomicBool::new(false));
-        let solution = Arc::new(Mutex::<(KeccakHash, u64)>::new((
-            KeccakHash::new_from_array([0; 32]),
-            0,
-        )));
+        hashes: Arc<AtomicU64>,
+        show_progress: bool,
+    ) -> Option<(KeccakHash, u64)> {
         let signer = self.signer();
-        let pubkey = signer.pubkey();
-        let thread_handles: Vec<_> = (0..threads)
-            .map(|i| {
-                std::thread::spawn({
-                    let found_solution = found_solution.clone();
-                    let solution = solution.clone();
-                    let mut stdout = stdout();
-                    move || {
-                        let n = u64::MAX.saturating_div(threads).saturating_mul(i);
-                        let mut next_hash: KeccakHash;
-                        let mut nonce: u64 = n;
-                        loop {
-                            next_hash = hashv(&[
-                                hash.to_bytes().as_slice(),
-                                pubkey.to_bytes().as_slice(),
-                                nonce.to_le_bytes().as_slice(),
-                            ]);
-                            if nonce % 10_000 == 0 {
-                                if found_solution.load(std::sync::atomic::Ordering::Relaxed) {
-                                    return;
-                                }
-                                if n == 0 {
-                                    stdout
-                                        .write_all(
-                                            format!("\r{}", next_hash.to_string()).as_bytes(),
-                                        )
-                                        .ok();
-                                }
-                            }
-                            if next_hash.le(&difficulty) {
-                                stdout
-                                    .write_all(format!("\r{}", next_hash.to_string()).as_bytes())
-                                    .ok();
-                                found_solution.store(true, std::sync::atomic::Ordering::Relaxed);
-                                let mut w_solution = solution.lock().expect("failed to lock mutex");
-                                *w_solution = (next_hash, nonce);
-                                return;
-                            }
-                            nonce += 1;
-                        }
+
+        // Resume from the last checkpoint for this challenge, if there is one
+        let checkpoint_path = self.checkpoint_path(signer.pubkey());
+        let checkpoint = self.load_checkpoint(signer.pubkey(), hash, threads);
+        self.emit(Event::SearchStarted {
+            challenge: hash.to_string(),
+            threads,
+            resumed: checkpoint.is_some(),
+        });
+        let ranges = match checkpoint {
+            Some(saved) => {
+                // A solution found before the last exit may never have been submitted
+                if let Some(nonce) = saved.solution {
+                    let next_hash = hashv(&[
+                        hash.as_ref(),
+                        signer.pubkey().as_ref(),
+                        nonce.to_le_bytes().as_slice(),
+                    ]);
+                    if self.validate_hash(next_hash, hash, signer.pubkey(), nonce, difficulty) {
+                        self.log("Resubmitting solution from checkpoint");
+                        return Some((next_hash, nonce));
                     }
-                })
-            })
-            .collect();
+                }
+                self.log("Resuming search from checkpoint");
+                saved.ranges
+            }
+            None => nonce_ranges(threads),
+        };
+        let ranges = Arc::new(ranges);
+        let checkpoints = tokio::spawn(save_checkpoints(
+            checkpoint_path.clone(),
+            signer.pubkey(),
+            hash,
+            ranges.clone(),
+            self.events.is_some(),
+        ));
 
-        for thread_handle in thread_handles {
-            thread_handle.join().unwrap();
+        let found_solution = Arc::new(AtomicBool::new(false));
+        let baseline = hashes.load(Ordering::Relaxed);
+        let progress = show_progress
+            .then(|| tokio::spawn(report_progress(hashes.clone(), baseline, difficulty)));
+        let watcher = tokio::spawn(watch_challenge(
+            self.rpc_client.clone(),
+            signer.pubkey(),
+            hash,
+            found_solution.clone(),
+        ));
+        let solution = tokio::task::spawn_blocking({
+            let pool = pool.clone();
+            let ranges = ranges.clone();
+            let authority = signer.pubkey();
+            move || pool.search(hash, authority, difficulty, ranges, found_solution, hashes)
+        })
+        .await
+        .expect("Search thread panicked");
+        if let Some(progress) = progress {
+            progress.abort();
         }
-
-        let r_solution = solution.lock().expect("Failed to get lock");
-        *r_solution
+        watcher.abort();
+        checkpoints.abort();
+        // Workers move past a solution, so it is kept until it has been submitted
+        save_checkpoint(
+            &checkpoint_path,
+            signer.pubkey(),
+            hash,
+            &ranges,
+            solution.map(|(_, nonce)| nonce),
+        )
+        .ok();
+        solution
     }
 
     pub fn validate_hash(
@@ -219,22 +735,7 @@ impl Miner {
         nonce: u64,
         difficulty: KeccakHash,
     ) -> bool {
-        // Validate hash correctness
-        let hash_ = hashv(&[
-            current_hash.as_ref(),
-            signer.as_ref(),
-            nonce.to_le_bytes().as_slice(),
-        ]);
-        if sol_memcmp(hash.as_ref(), hash_.as_ref(), HASH_BYTES) != 0 {
-            return false;
-        }
-
-        // Validate hash difficulty
-        if hash.gt(&difficulty) {
-            return false;
-        }
-
-        true
+        validate_hash(hash, current_hash, signer, nonce, difficulty)
     }
 
     pub async fn get_ore_display_balance(&self) -> String {
@@ -256,3 +757,81 @@ impl Miner {
         }
     }
 }
+
+/// Checks that `hash` is the keccak of the challenge, authority and nonce, and
+/// is at or below `difficulty`.
+pub fn validate_hash(
+    hash: KeccakHash,
+    current_hash: KeccakHash,
+    signer: Pubkey,
+    nonce: u64,
+    difficulty: KeccakHash,
+) -> bool {
+    // Validate hash correctness
+    let hash_ = hashv(&[
+        current_hash.as_ref(),
+        signer.as_ref(),
+        nonce.to_le_bytes().as_slice(),
+    ]);
+    if sol_memcmp(hash.as_ref(), hash_.as_ref(), HASH_BYTES) != 0 {
+        return false;
+    }
+
+    // Validate hash difficulty
+    if hash.gt(&difficulty) {
+        return false;
+    }
+
+    true
+}
+
+/// Polls the proof account while a search is running and sets `found_solution`
+/// to stop the workers if its challenge no longer matches `hash`.
+async fn watch_challenge(
+    client: Arc<RpcClient>,
+    authority: Pubkey,
+    hash: KeccakHash,
+    found_solution: Arc<AtomicBool>,
+) {
+    let proof_address = proof_pubkey(authority);
+    loop {
+        tokio::time::sleep(CHALLENGE_POLL_INTERVAL).await;
+        let Ok(data) = client.get_account_data(&proof_address).await else {
+            continue;
+        };
+        if let Ok(proof) = Proof::try_from_bytes(&data) {
+            if KeccakHash::from(proof.hash).ne(&hash) {
+                found_solution.store(true, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+}
+
+/// The first characters of a pubkey, to tell authorities apart in output.
+fn short_pubkey(pubkey: Pubkey) -> String {
+    pubkey.to_string().chars().take(8).collect()
+}
+
+/// Prints each authority's hash rate and solutions once a minute.
+async fn report_authorities(authorities: Vec<(Pubkey, Arc<SessionStats>)>, events: bool) {
+    let mut last = vec![0u64; authorities.len()];
+    loop {
+        tokio::time::sleep(AUTHORITY_REPORT_INTERVAL).await;
+        for ((authority, stats), last) in authorities.iter().zip(last.iter_mut()) {
+            let hashes = stats.hashes.load(Ordering::Relaxed);
+            let rate = (hashes - *last) as f64 / AUTHORITY_REPORT_INTERVAL.as_secs_f64();
+            *last = hashes;
+            log(
+                events,
+                format!(
+                    "[{}] {}H/s  Solutions: {}  Landed: {}",
+                    short_pubkey(*authority),
+                    format_count(rate),
+                    stats.solutions.load(Ordering::Relaxed),
+                    stats.landed.load(Ordering::Relaxed),
+                ),
+            );
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    io::{stderr, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use solana_sdk::{keccak::Hash as KeccakHash, signature::Signer};
+use tokio::{net::TcpStream, sync::mpsc};
+
+use crate::{
+    backend::HashBackend,
+    bench::available_threads,
+    mine::{ThreadCount, ThreadTuner},
+    progress::format_count,
+    protocol::{read_message, write_message, LineReader, Message},
+    schedule::{enforce_schedule, Schedule},
+    worker::{RemoteSearch, RemoteWork, SearchEvent, RECONNECT_DELAY},
+    workers::{WorkerOptions, WorkerPool},
+    Miner,
+};
+
+// How often the share counts are redrawn
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Share counts for the whole session, across reconnects.
+#[derive(Default)]
+struct ShareStats {
+    hashes: Arc<AtomicU64>,
+    accepted: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl Miner {
+    /// Mines for a pool instead of on our own proof. The pool hands out
+    /// challenges and nonce ranges, and every hash at or below its share
+    /// difficulty is sent back to be credited to this keypair.
+    pub async fn mine_pool(
+        &self,
+        addr: String,
+        threads: ThreadCount,
+        recalibrate_interval: Duration,
+        backend: Arc<dyn HashBackend>,
+        options: WorkerOptions,
+        schedule: Option<Schedule>,
+    ) {
+        let member = self.signer().pubkey();
+        let pool_size = match threads {
+            ThreadCount::Fixed(n) => n,
+            ThreadCount::Auto => available_threads(),
+        };
+        let pool = self.worker_pool(&backend, pool_size, options);
+        if let Some(schedule) = schedule {
+            tokio::spawn(enforce_schedule(
+                Arc::new(schedule),
+                pool.clone(),
+                self.events.is_some(),
+            ));
+        }
+        let tuner = ThreadTuner::new(threads, recalibrate_interval, self.events.is_some());
+        self.log(format!("Member: {}", member));
+        self.log(format!("Threads: {}", tuner.thread_count(&pool).await));
+        self.log(format!(
+            "Mining for pool {} ({} backend)",
+            addr,
+            backend.name()
+        ));
+
+        let stats = Arc::new(ShareStats::default());
+        tokio::spawn(report_shares(stats.clone()));
+        loop {
+            match TcpStream::connect(&addr).await {
+                Ok(stream) => {
+                    if let Err(err) = self.serve_pool(&pool, &tuner, stream, &stats).await {
+                        self.error(format!("\nConnection to pool lost: {}", err));
+                    } else {
+                        self.log("\nPool closed the connection");
+                    }
+                }
+                Err(err) => self.error(format!("\nFailed to connect to {}: {}", addr, err)),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn serve_pool(
+        &self,
+        pool: &Arc<WorkerPool>,
+        tuner: &ThreadTuner,
+        stream: TcpStream,
+        stats: &ShareStats,
+    ) -> std::io::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = LineReader::new(reader);
+        let join = Message::Join {
+            member: self.signer().pubkey().to_string(),
+            threads: tuner.thread_count(pool).await,
+        };
+        write_message(&mut writer, &join).await?;
+
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        let mut current: Option<(RemoteSearch, KeccakHash)> = None;
+        let mut search_id = 0u64;
+        let result = loop {
+            tokio::select! {
+                message = read_message(&mut lines) => match message {
+                    Ok(Some(Message::Accepted { .. })) => {
+                        stats.accepted.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Ok(Some(Message::Rejected { nonce, reason })) => {
+                        stats.rejected.fetch_add(1, Ordering::Relaxed);
+                        self.log(format!("\nShare {} rejected: {}", nonce, reason));
+                    }
+                    Ok(Some(message)) => {
+                        let Some(work) = RemoteWork::parse(message) else {
+                            continue;
+                        };
+                        if let Some((search, _)) = current.take() {
+                            search.cancel();
+                        }
+                        search_id += 1;
+                        let challenge = work.challenge;
+                        // Re-calibrates between searches, if it is time to
+                        let threads = tuner.thread_count(pool).await;
+                        let search = RemoteSearch::spawn(
+                            pool,
+                            work,
+                            search_id,
+                            threads,
+                            true,
+                            stats.hashes.clone(),
+                            event_tx.clone(),
+                        );
+                        current = Some((search, challenge));
+                    }
+                    Ok(None) => break Ok(()),
+                    Err(err) => break Err(err),
+                },
+                Some((id, event)) = event_rx.recv() => {
+                    // Ignore results from searches that were replaced by newer work
+                    let Some(challenge) = current.as_ref().filter(|_| id == search_id).map(|(_, c)| *c) else {
+                        continue;
+                    };
+                    let message = match event {
+                        SearchEvent::Found(hash, nonce) => Message::Share {
+                            challenge: hex::encode(challenge),
+                            nonce,
+                            hash: hex::encode(hash),
+                        },
+                        SearchEvent::Exhausted => {
+                            current = None;
+                            Message::Exhausted
+                        }
+                    };
+                    if let Err(err) = write_message(&mut writer, &message).await {
+                        break Err(err);
+                    }
+                }
+            }
+        };
+        if let Some((search, _)) = current.take() {
+            search.cancel();
+        }
+        result
+    }
+}
+
+/// Redraws the session's hash rate and share counts once a second.
+async fn report_shares(stats: Arc<ShareStats>) {
+    let started = Instant::now();
+    let mut stderr = stderr();
+    loop {
+        tokio::time::sleep(STATUS_INTERVAL).await;
+        let rate = stats.hashes.load(Ordering::Relaxed) as f64 / started.elapsed().as_secs_f64();
+        stderr
+            .write_all(
+                format!(
+                    "\r\x1b[KRate: {}H/s  Shares: {} accepted, {} rejected",
+                    format_count(rate),
+                    stats.accepted.load(Ordering::Relaxed),
+                    stats.rejected.load(Ordering::Relaxed),
+                )
+                .as_bytes(),
+            )
+            .ok();
+        stderr.flush().ok();
+    }
+}
@@ -0,0 +1,266 @@
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{keccak::Hash as KeccakHash, signature::Signer};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, watch},
+};
+
+use crate::{
+    dispatch::{accept_connections, RangeAllocator, Solution},
+    mine::validate_hash,
+    progress::expected_hashes,
+    protocol::{read_message, write_message, LineReader, Message},
+    shares::{load_json, save_json, save_shares, ShareLedger},
+    solve::parse_hex_hash,
+    Miner,
+};
+
+// The most ranges a member may hold for one challenge. A range takes hours to
+// search, so only a misbehaving member asks for this many.
+const MAX_MEMBER_RANGES: usize = 16;
+
+/// The challenge members are currently searching, the nonces handed out so far,
+/// and the shares already credited for it.
+struct Round {
+    challenge: KeccakHash,
+    authority: Pubkey,
+    difficulty: KeccakHash,
+    share_difficulty: KeccakHash,
+    ranges: RangeAllocator,
+    seen: Mutex<HashSet<u64>>,
+}
+
+impl Round {
+    fn work(&self, (start, end): (u64, u64)) -> Message {
+        Message::Work {
+            challenge: hex::encode(self.challenge),
+            authority: self.authority.to_string(),
+            difficulty: hex::encode(self.share_difficulty),
+            start,
+            end,
+        }
+    }
+}
+
+/// Everything a member connection needs from the server.
+#[derive(Clone)]
+struct PoolState {
+    rounds: watch::Receiver<Option<Arc<Round>>>,
+    solutions: mpsc::UnboundedSender<Solution>,
+    ledger: Arc<Mutex<ShareLedger>>,
+    connected: Arc<AtomicUsize>,
+}
+
+impl Miner {
+    /// Mines with this keypair as the pool authority. Members search disjoint
+    /// nonce ranges and are credited for every hash they find at or below
+    /// `share_difficulty`; hashes that also meet the real difficulty are submitted.
+    pub async fn pool_serve(&self, listen: String, share_difficulty: KeccakHash) {
+        self.register().await;
+        let signer = self.signer();
+        let listener = TcpListener::bind(&listen)
+            .await
+            .expect("Failed to bind listen address");
+        println!("Pool {} listening on {}", signer.pubkey(), listen);
+
+        // Load the share ledger and keep it saved
+        let ledger_path = self.pool_dir(signer.pubkey()).join("shares.json");
+        let ledger = Arc::new(Mutex::new(load_json::<ShareLedger>(&ledger_path)));
+        tokio::spawn(save_shares(ledger_path.clone(), ledger.clone()));
+
+        // Accept members in the background
+        let (round_tx, round_rx) = watch::channel::<Option<Arc<Round>>>(None);
+        let (solution_tx, solution_rx) = mpsc::unbounded_channel();
+        let state = PoolState {
+            rounds: round_rx,
+            solutions: solution_tx,
+            ledger: ledger.clone(),
+            connected: Arc::new(AtomicUsize::new(0)),
+        };
+        accept_connections(listener, {
+            let state = state.clone();
+            move |stream, addr| handle_member(stream, addr, state.clone())
+        });
+
+        // Shares can never be harder than a real solution, or members would
+        // hold back solutions
+        self.serve_challenges(
+            solution_rx,
+            |challenge, difficulty| {
+                round_tx.send_replace(Some(Arc::new(Round {
+                    challenge,
+                    authority: signer.pubkey(),
+                    difficulty,
+                    share_difficulty: share_difficulty.max(difficulty),
+                    ranges: RangeAllocator::default(),
+                    seen: Mutex::new(HashSet::new()),
+                })));
+                println!(
+                    "Challenge {} sent to {} member(s)",
+                    challenge,
+                    state.connected.load(Ordering::Relaxed)
+                );
+            },
+            || {
+                if let Err(err) = save_json(&ledger_path, &*ledger.lock().unwrap()) {
+                    println!("Failed to save share ledger: {}", err);
+                }
+            },
+        )
+        .await;
+    }
+}
+
+/// Hands nonce ranges to one member and credits the shares it returns.
+async fn handle_member(stream: TcpStream, addr: SocketAddr, mut state: PoolState) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = LineReader::new(reader);
+
+    // Members must say which wallet to credit before they get any work
+    let member = match read_message(&mut lines).await {
+        Ok(Some(Message::Join { member, threads })) => match Pubkey::from_str(&member) {
+            Ok(member) => {
+                println!(
+                    "Member {} joined from {} with {} threads",
+                    member, addr, threads
+                );
+                member
+            }
+            Err(_) => return,
+        },
+        _ => return,
+    };
+    state.connected.fetch_add(1, Ordering::Relaxed);
+
+    // The round this member is working on, and the ranges it was given for it
+    let mut round: Option<Arc<Round>> = None;
+    let mut ranges: Vec<(u64, u64)> = vec![];
+    let current = state.rounds.borrow_and_update().clone();
+    let mut reply = assign(&mut round, &mut ranges, current);
+    loop {
+        match reply {
+            Ok(Some(message)) => {
+                if write_message(&mut writer, &message).await.is_err() {
+                    break;
+                }
+            }
+            Ok(None) => {}
+            Err(reason) => {
+                println!("Member {} {}", member, reason);
+                break;
+            }
+        }
+        reply = tokio::select! {
+            changed = state.rounds.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                let current = state.rounds.borrow_and_update().clone();
+                assign(&mut round, &mut ranges, current)
+            }
+            message = read_message(&mut lines) => match message {
+                Ok(Some(Message::Exhausted)) => {
+                    let current = state.rounds.borrow().clone();
+                    assign(&mut round, &mut ranges, current)
+                }
+                Ok(Some(Message::Share { challenge, nonce, hash })) => {
+                    let current = state.rounds.borrow().clone();
+                    Ok(Some(match check_share(&state, member, current, &ranges, &challenge, nonce, &hash) {
+                        Ok(()) => Message::Accepted { nonce },
+                        Err(reason) => Message::Rejected { nonce, reason: reason.to_string() },
+                    }))
+                }
+                Ok(Some(_)) => Ok(None),
+                Ok(None) => break,
+                Err(err) => {
+                    println!("Member {} sent an invalid message: {}", member, err);
+                    break;
+                }
+            },
+        };
+    }
+    state.connected.fetch_sub(1, Ordering::Relaxed);
+    println!("Member {} disconnected", member);
+}
+
+/// Hands out the next range of the current round, forgetting the member's
+/// ranges from earlier rounds. Fails if the member already holds
+/// `MAX_MEMBER_RANGES` ranges of this round.
+fn assign(
+    round: &mut Option<Arc<Round>>,
+    ranges: &mut Vec<(u64, u64)>,
+    current: Option<Arc<Round>>,
+) -> Result<Option<Message>, &'static str> {
+    let Some(current) = current else {
+        return Ok(None);
+    };
+    if !round.as_ref().is_some_and(|r| Arc::ptr_eq(r, &current)) {
+        ranges.clear();
+    }
+    if ranges.len() >= MAX_MEMBER_RANGES {
+        return Err("asked for too many ranges");
+    }
+    let work = current.ranges.next_range().map(|range| {
+        ranges.push(range);
+        current.work(range)
+    });
+    *round = Some(current);
+    Ok(work)
+}
+
+/// Credits a share if it is a fresh, valid hash from one of the member's ranges,
+/// and forwards it to be submitted if it also solves the challenge.
+fn check_share(
+    state: &PoolState,
+    member: Pubkey,
+    current: Option<Arc<Round>>,
+    ranges: &[(u64, u64)],
+    challenge: &str,
+    nonce: u64,
+    hash: &str,
+) -> Result<(), &'static str> {
+    let (Ok(challenge), Ok(hash)) = (parse_hex_hash(challenge), parse_hex_hash(hash)) else {
+        return Err("malformed");
+    };
+    let Some(round) = current.filter(|r| r.challenge.eq(&challenge)) else {
+        return Err("stale");
+    };
+    if !ranges
+        .iter()
+        .any(|(start, end)| (*start..*end).contains(&nonce))
+    {
+        return Err("nonce outside assigned range");
+    }
+    if !validate_hash(
+        hash,
+        round.challenge,
+        round.authority,
+        nonce,
+        round.share_difficulty,
+    ) {
+        return Err("invalid");
+    }
+    if !round.seen.lock().unwrap().insert(nonce) {
+        return Err("duplicate");
+    }
+    state
+        .ledger
+        .lock()
+        .unwrap()
+        .credit(member, expected_hashes(round.share_difficulty));
+    if hash.le(&round.difficulty) {
+        println!("Member {} found a solution", member);
+        state.solutions.send((challenge, hash, nonce)).ok();
+    }
+    Ok(())
+}
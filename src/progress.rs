@@ -0,0 +1,95 @@
+use std::{
+    io::{stderr, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use solana_sdk::keccak::Hash as KeccakHash;
+
+// How often the status line is redrawn while mining
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The probability that a single hash is at or below `difficulty`.
+pub fn hash_success_probability(difficulty: KeccakHash) -> f64 {
+    // Hashes compare as big-endian numbers, so each byte is worth 1/256 of the one before it.
+    let mut p = 0f64;
+    let mut scale = 1f64;
+    for byte in difficulty.to_bytes() {
+        scale /= 256.0;
+        p += byte as f64 * scale;
+    }
+    // Count the difficulty value itself, which is a valid hash.
+    (p + scale).min(1.0)
+}
+
+/// The expected number of hashes needed to find a solution.
+pub fn expected_hashes(difficulty: KeccakHash) -> f64 {
+    1.0 / hash_success_probability(difficulty)
+}
+
+/// The probability that at least one of `hashes` attempts would have found a solution.
+pub fn solve_probability(difficulty: KeccakHash, hashes: u64) -> f64 {
+    let p = hash_success_probability(difficulty);
+    -((hashes as f64) * (-p).ln_1p()).exp_m1()
+}
+
+/// Redraws a status line with the search's progress until the task is aborted.
+/// Only hashes counted into `hashes` beyond `baseline` belong to this search.
+pub async fn report_progress(hashes: Arc<AtomicU64>, baseline: u64, difficulty: KeccakHash) {
+    let started = Instant::now();
+    let expected = expected_hashes(difficulty);
+    let mut stderr = stderr();
+    loop {
+        tokio::time::sleep(PROGRESS_INTERVAL).await;
+        let done = hashes.load(Ordering::Relaxed).saturating_sub(baseline);
+        let rate = done as f64 / started.elapsed().as_secs_f64();
+        let eta = if rate > 0.0 {
+            format_duration(Duration::from_secs_f64(
+                (expected / rate).min(u32::MAX as f64),
+            ))
+        } else {
+            "-".to_string()
+        };
+        stderr
+            .write_all(
+                format!(
+                    "\r\x1b[KHashes: {}  Rate: {}H/s  Expected time: {}  Solved by now: {:.1}%",
+                    format_count(done as f64),
+                    format_count(rate),
+                    eta,
+                    solve_probability(difficulty, done) * 100.0,
+                )
+                .as_bytes(),
+            )
+            .ok();
+        stderr.flush().ok();
+    }
+}
+
+/// Formats a count with an SI suffix, e.g. `1.23 M`.
+pub fn format_count(n: f64) -> String {
+    let units = ["", "k", "M", "G", "T"];
+    let mut n = n;
+    let mut unit = 0;
+    while n >= 1000.0 && unit < units.len() - 1 {
+        n /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", n, units[unit])
+}
+
+/// Formats a duration as hours, minutes and seconds, e.g. `1h 02m 03s`.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (h, m, s) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if h > 0 {
+        format!("{}h {:02}m {:02}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m {:02}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
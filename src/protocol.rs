@@ -0,0 +1,149 @@
+use std::io::{Error, ErrorKind};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::tcp::OwnedReadHalf,
+};
+
+// The longest line a peer may send. Every message fits in a few hundred bytes.
+const MAX_LINE_LENGTH: usize = 4096;
+
+/// Messages exchanged between a coordinator and its workers, or a pool and its
+/// members, one JSON object per line.
+/// Hashes are hex encoded, as in `ore solve`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// Sent by a worker once it connects
+    Hello { threads: u64 },
+
+    /// A nonce range to search for a challenge
+    Work {
+        challenge: String,
+        authority: String,
+        difficulty: String,
+        start: u64,
+        end: u64,
+    },
+
+    /// A hash at or below the work's difficulty
+    Solution {
+        challenge: String,
+        nonce: u64,
+        hash: String,
+    },
+
+    /// The worker searched its whole range without finding a solution
+    Exhausted,
+
+    /// Sent by a pool member once it connects, naming the wallet to pay
+    Join { member: String, threads: u64 },
+
+    /// A hash at or below the pool's share difficulty
+    Share {
+        challenge: String,
+        nonce: u64,
+        hash: String,
+    },
+
+    /// The pool credited a share
+    Accepted { nonce: u64 },
+
+    /// The pool refused a share
+    Rejected { nonce: u64, reason: String },
+}
+
+/// The lines a peer sends, keeping a partly read line between reads.
+pub struct LineReader {
+    reader: BufReader<OwnedReadHalf>,
+    line: Vec<u8>,
+}
+
+impl LineReader {
+    pub fn new(reader: OwnedReadHalf) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            line: vec![],
+        }
+    }
+}
+
+/// Reads the next message, or `None` once the peer disconnects. Fails if the
+/// peer sends a line longer than `MAX_LINE_LENGTH`. Safe to cancel, as in
+/// `tokio::select!`: a partly read line is kept for the next call.
+pub async fn read_message(lines: &mut LineReader) -> std::io::Result<Option<Message>> {
+    loop {
+        let limit = (MAX_LINE_LENGTH + 1).saturating_sub(lines.line.len());
+        let read = (&mut lines.reader)
+            .take(limit as u64)
+            .read_until(b'\n', &mut lines.line)
+            .await?;
+        if lines.line.last() == Some(&b'\n') {
+            let line = std::mem::take(&mut lines.line);
+            return serde_json::from_slice(&line)
+                .map(Some)
+                .map_err(|err| Error::new(ErrorKind::InvalidData, err));
+        }
+        if lines.line.len() > MAX_LINE_LENGTH {
+            return Err(Error::new(ErrorKind::InvalidData, "line too long"));
+        }
+        if read == 0 {
+            return Ok(None);
+        }
+    }
+}
+
+pub async fn write_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &Message,
+) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+
+    async fn connect() -> (TcpStream, LineReader) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        let (reader, _writer) = server.into_split();
+        (client, LineReader::new(reader))
+    }
+
+    #[tokio::test]
+    async fn reads_messages_until_disconnect() {
+        let (mut client, mut lines) = connect().await;
+        client
+            .write_all(b"{\"type\":\"exhausted\"}\n{\"type\":\"hello\",\"threads\":4}\n")
+            .await
+            .unwrap();
+        drop(client);
+        assert!(matches!(
+            read_message(&mut lines).await.unwrap(),
+            Some(Message::Exhausted)
+        ));
+        assert!(matches!(
+            read_message(&mut lines).await.unwrap(),
+            Some(Message::Hello { threads: 4 })
+        ));
+        assert!(read_message(&mut lines).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_long_lines() {
+        let (mut client, mut lines) = connect().await;
+        let line = vec![b' '; MAX_LINE_LENGTH + 1];
+        client.write_all(&line).await.unwrap();
+        let err = read_message(&mut lines).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
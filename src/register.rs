@@ -12,8 +12,18 @@ impl Miner {
             return;
         }
 
+        // A dry run only checks that registration would succeed
+        if self.dry_run {
+            self.log("Simulating registration...");
+            let ix = ore::instruction::register(signer.pubkey());
+            if let Err(err) = self.send_and_confirm(&[ix], true, false).await {
+                self.error(format!("Registration would fail: {}", err));
+            }
+            return;
+        }
+
         // Sign and send transaction.
-        println!("Generating challenge...");
+        self.log("Generating challenge...");
         'send: loop {
             let ix = ore::instruction::register(signer.pubkey());
             if self.send_and_confirm(&[ix], true, false).await.is_ok() {
@@ -0,0 +1,371 @@
+use std::{fs, path::Path, sync::Arc, time::Duration};
+
+use chrono::{Datelike, Local, NaiveDateTime, Timelike};
+
+use crate::{
+    events::{emit, log, Event},
+    workers::WorkerPool,
+};
+
+// How often the schedule is re-checked while mining
+const SCHEDULE_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// The times mining is allowed, read from a schedule file. Each non-empty line
+/// that is not a `#` comment adds one window, and mining is allowed whenever
+/// any window is open. A line is either a weekday window such as
+/// `mon-fri 18:00-08:00` (windows that end before they start run past
+/// midnight, and day ranges such as `fri-mon` may wrap past Sunday), or a five
+/// field cron expression such as `* 0-7,18-23 * * 1-5`,
+/// which allows every minute it matches.
+pub struct Schedule {
+    rules: Vec<Rule>,
+}
+
+enum Rule {
+    Window {
+        days: [bool; 7],
+        start: u32,
+        end: u32,
+    },
+    Cron {
+        minutes: Vec<bool>,
+        hours: Vec<bool>,
+        days_of_month: Vec<bool>,
+        months: Vec<bool>,
+        weekdays: Vec<bool>,
+        any_day_of_month: bool,
+        any_weekday: bool,
+    },
+}
+
+impl Schedule {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::parse(&data)
+    }
+
+    pub fn parse(data: &str) -> Result<Self, String> {
+        let mut rules = vec![];
+        for (i, line) in data.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let rule = parse_rule(line).map_err(|err| format!("line {}: {}", i + 1, err))?;
+            rules.push(rule);
+        }
+        if rules.is_empty() {
+            return Err("schedule has no mining windows".to_string());
+        }
+        Ok(Self { rules })
+    }
+
+    /// Returns true if mining is allowed at `time`.
+    pub fn is_open(&self, time: NaiveDateTime) -> bool {
+        self.rules.iter().any(|rule| rule.is_open(time))
+    }
+
+    /// Returns the next minute at which the schedule changes from `time`'s
+    /// state, looking up to a year ahead.
+    pub fn next_change(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let open = self.is_open(time);
+        let mut t = time.with_second(0)?.with_nanosecond(0)?;
+        for _ in 0..366 * 24 * 60 {
+            t += chrono::Duration::minutes(1);
+            if self.is_open(t) != open {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+impl Rule {
+    fn is_open(&self, time: NaiveDateTime) -> bool {
+        let weekday = time.weekday().num_days_from_sunday() as usize;
+        let minute = time.hour() * 60 + time.minute();
+        match self {
+            Rule::Window { days, start, end } => {
+                if start < end {
+                    days[weekday] && (*start..*end).contains(&minute)
+                } else {
+                    // The window runs past midnight into the next day
+                    (days[weekday] && minute >= *start)
+                        || (days[(weekday + 6) % 7] && minute < *end)
+                }
+            }
+            Rule::Cron {
+                minutes,
+                hours,
+                days_of_month,
+                months,
+                weekdays,
+                any_day_of_month,
+                any_weekday,
+            } => {
+                let day_of_month = days_of_month[time.day() as usize];
+                let day_of_week = weekdays[weekday];
+                // As in cron, if both day fields are restricted either one may match
+                let day = match (any_day_of_month, any_weekday) {
+                    (true, true) => true,
+                    (true, false) => day_of_week,
+                    (false, true) => day_of_month,
+                    (false, false) => day_of_month || day_of_week,
+                };
+                minutes[time.minute() as usize]
+                    && hours[time.hour() as usize]
+                    && months[time.month() as usize]
+                    && day
+            }
+        }
+    }
+}
+
+fn parse_rule(line: &str) -> Result<Rule, String> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    match fields.as_slice() {
+        [days, window] => {
+            let (start, end) = window
+                .split_once('-')
+                .ok_or_else(|| format!("expected HH:MM-HH:MM, got `{}`", window))?;
+            let mut weekdays = parse_field(days, 0, 7, &WEEKDAYS, Some(7))?;
+            // Both 0 and 7 mean Sunday
+            weekdays[0] |= weekdays[7];
+            let mut days = [false; 7];
+            days.copy_from_slice(&weekdays[..7]);
+            Ok(Rule::Window {
+                days,
+                start: parse_time(start)?,
+                end: parse_time(end)?,
+            })
+        }
+        [minutes, hours, days_of_month, months, weekdays] => {
+            let mut weekdays_ = parse_field(weekdays, 0, 7, &WEEKDAYS, Some(7))?;
+            weekdays_[0] |= weekdays_[7];
+            Ok(Rule::Cron {
+                minutes: parse_field(minutes, 0, 59, &[], None)?,
+                hours: parse_field(hours, 0, 23, &[], None)?,
+                days_of_month: parse_field(days_of_month, 1, 31, &[], None)?,
+                months: parse_field(months, 1, 12, &MONTHS, None)?,
+                weekdays: weekdays_,
+                any_day_of_month: days_of_month.eq(&"*"),
+                any_weekday: weekdays.eq(&"*"),
+            })
+        }
+        _ => Err(format!(
+            "expected `DAYS HH:MM-HH:MM` or a five field cron expression, got `{}`",
+            line
+        )),
+    }
+}
+
+/// Parses `HH:MM` into minutes after midnight. `24:00` is allowed as an end time.
+fn parse_time(s: &str) -> Result<u32, String> {
+    let invalid = || format!("invalid time `{}`", s);
+    let (h, m) = s.split_once(':').ok_or_else(invalid)?;
+    let h: u32 = h.parse().map_err(|_| invalid())?;
+    let m: u32 = m.parse().map_err(|_| invalid())?;
+    if m >= 60 || h > 24 || (h == 24 && m > 0) {
+        return Err(invalid());
+    }
+    Ok(h * 60 + m)
+}
+
+/// Parses a cron field (`*`, `a`, `a-b`, `*/n`, `a-b/n` and comma separated
+/// lists of those) into a table indexed by value. `names` are accepted in
+/// place of numbers, starting from `min`. If the field's values repeat every
+/// `cycle`, a range may wrap around, as in `fri-mon`.
+fn parse_field(
+    field: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+    cycle: Option<u32>,
+) -> Result<Vec<bool>, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let lower = s.to_lowercase();
+        if let Some(i) = names.iter().position(|name| lower.starts_with(name)) {
+            return Ok(min + i as u32);
+        }
+        match s.parse::<u32>() {
+            Ok(v) if (min..=max).contains(&v) => Ok(v),
+            _ => Err(format!("invalid value `{}` in `{}`", s, field)),
+        }
+    };
+    let mut table = vec![false; max as usize + 1];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step in `{}`", field))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" | "daily" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        let end = match cycle {
+            Some(cycle) if start > end => end + cycle,
+            _ if start > end => return Err(format!("invalid range `{}`", range)),
+            _ => end,
+        };
+        for v in (start..=end).step_by(step as usize) {
+            let v = match cycle {
+                Some(cycle) if v > max => v - cycle,
+                _ => v,
+            };
+            table[v as usize] = true;
+        }
+    }
+    Ok(table)
+}
+
+/// Suspends the worker pool whenever the schedule is closed, and resumes it when
+/// the next window opens. Runs until the task is aborted. With `events`, each
+/// change is also written as an event.
+pub async fn enforce_schedule(schedule: Arc<Schedule>, pool: Arc<WorkerPool>, events: bool) {
+    loop {
+        let now = Local::now().naive_local();
+        let open = schedule.is_open(now);
+        if open == pool.is_paused() {
+            let until = schedule
+                .next_change(now)
+                .map(|t| t.format(" until %a %H:%M").to_string())
+                .unwrap_or_default();
+            if open {
+                log(events, format!("\nMining window open{}, resuming", until));
+            } else {
+                log(
+                    events,
+                    format!("\nOutside mining window, suspending{}", until),
+                );
+            }
+            if events {
+                emit(None, &Event::ScheduleChanged { open });
+            }
+            pool.set_paused(!open);
+        }
+        tokio::time::sleep(SCHEDULE_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    // 2024-01-01 was a Monday
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_rules_and_comments() {
+        let schedule =
+            Schedule::parse("# weekends\nsat-sun 00:00-24:00\n\n*/30 9 * * mon-fri # mornings\n")
+                .unwrap();
+        assert_eq!(schedule.rules.len(), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_schedules() {
+        assert!(Schedule::parse("").is_err());
+        assert!(Schedule::parse("# only a comment").is_err());
+        assert!(Schedule::parse("mon 18:00").is_err());
+        assert!(Schedule::parse("mon 25:00-26:00").is_err());
+        assert!(Schedule::parse("mon 10:60-11:00").is_err());
+        assert!(Schedule::parse("xyz 10:00-11:00").is_err());
+        assert!(Schedule::parse("* 18-9 * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+    }
+
+    #[test]
+    fn weekday_window() {
+        let schedule = Schedule::parse("mon-fri 09:00-17:00").unwrap();
+        assert!(schedule.is_open(at(1, 9, 0)));
+        assert!(schedule.is_open(at(5, 16, 59)));
+        assert!(!schedule.is_open(at(5, 17, 0)));
+        assert!(!schedule.is_open(at(3, 8, 59)));
+        assert!(!schedule.is_open(at(6, 12, 0)));
+    }
+
+    #[test]
+    fn window_past_midnight() {
+        let schedule = Schedule::parse("mon-fri 18:00-08:00").unwrap();
+        assert!(schedule.is_open(at(1, 18, 0)));
+        assert!(schedule.is_open(at(2, 7, 59)));
+        assert!(!schedule.is_open(at(2, 8, 0)));
+        // Friday night runs into Saturday morning, but Monday morning is closed
+        assert!(schedule.is_open(at(6, 3, 0)));
+        assert!(!schedule.is_open(at(1, 3, 0)));
+    }
+
+    #[test]
+    fn weekday_ranges_wrap_past_sunday() {
+        let weekend = Schedule::parse("sat-sun 00:00-24:00").unwrap();
+        assert!(weekend.is_open(at(6, 0, 0)));
+        assert!(weekend.is_open(at(7, 23, 59)));
+        assert!(!weekend.is_open(at(5, 12, 0)));
+        assert!(!weekend.is_open(at(8, 0, 0)));
+
+        let long_weekend = Schedule::parse("fri-mon 18:00-08:00").unwrap();
+        assert!(long_weekend.is_open(at(5, 20, 0)));
+        assert!(long_weekend.is_open(at(7, 20, 0)));
+        assert!(long_weekend.is_open(at(8, 20, 0)));
+        assert!(long_weekend.is_open(at(9, 7, 0)));
+        assert!(!long_weekend.is_open(at(9, 20, 0)));
+        assert!(!long_weekend.is_open(at(4, 20, 0)));
+
+        let cron = Schedule::parse("* * * * sat-sun").unwrap();
+        assert!(cron.is_open(at(7, 12, 0)));
+        assert!(!cron.is_open(at(8, 12, 0)));
+    }
+
+    #[test]
+    fn sunday_is_0_or_7() {
+        for days in ["sun", "0", "7"] {
+            let schedule = Schedule::parse(&format!("{} 10:00-11:00", days)).unwrap();
+            assert!(schedule.is_open(at(7, 10, 30)));
+            assert!(!schedule.is_open(at(1, 10, 30)));
+        }
+    }
+
+    #[test]
+    fn cron_expression() {
+        let schedule = Schedule::parse("*/15 0-7,18-23 * * 1-5").unwrap();
+        assert!(schedule.is_open(at(1, 18, 15)));
+        assert!(!schedule.is_open(at(1, 18, 16)));
+        assert!(!schedule.is_open(at(1, 12, 0)));
+        assert!(!schedule.is_open(at(6, 18, 15)));
+
+        // With both day fields restricted, either may match
+        let schedule = Schedule::parse("0 12 1 * sun").unwrap();
+        assert!(schedule.is_open(at(1, 12, 0)));
+        assert!(schedule.is_open(at(7, 12, 0)));
+        assert!(!schedule.is_open(at(2, 12, 0)));
+    }
+
+    #[test]
+    fn next_change() {
+        let schedule = Schedule::parse("mon-fri 09:00-17:00").unwrap();
+        assert_eq!(schedule.next_change(at(1, 8, 30)), Some(at(1, 9, 0)));
+        assert_eq!(schedule.next_change(at(5, 12, 0)), Some(at(5, 17, 0)));
+        assert_eq!(schedule.next_change(at(5, 18, 0)), Some(at(8, 9, 0)));
+    }
+}
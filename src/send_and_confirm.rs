@@ -1,31 +1,46 @@
 use std::{
     io::{stdout, Write},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use solana_client::{
     client_error::{ClientError, ClientErrorKind, Result as ClientResult},
     rpc_config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig},
+    rpc_request::{RpcError, RpcResponseErrorData},
 };
-use solana_program::instruction::Instruction;
+use solana_program::{instruction::Instruction, native_token::lamports_to_sol};
 use solana_sdk::{
     commitment_config::CommitmentLevel,
     compute_budget::ComputeBudgetInstruction,
     signature::{Signature, Signer},
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 use solana_transaction_status::{TransactionConfirmationStatus, UiTransactionEncoding};
 
-use crate::Miner;
+use crate::{
+    events::Event,
+    fees::{compute_unit_price, with_compute_unit_price},
+    session::transaction_fee,
+    Miner,
+};
 
 const RPC_RETRIES: usize = 0;
 const SIMULATION_RETRIES: usize = 4;
-const GATEWAY_RETRIES: usize = 4;
-const CONFIRM_RETRIES: usize = 4;
 
-const CONFIRM_DELAY: u64 = 5000;
+const CONFIRM_TIMEOUT: u64 = 20000;
 const GATEWAY_DELAY: u64 = 2000;
 
+/// A sent transaction and what it cost.
+pub struct TxReceipt {
+    pub signature: Signature,
+
+    /// Lamports paid in fees
+    pub fee: u64,
+
+    /// Compute units the transaction used in simulation
+    pub compute_units: Option<u64>,
+}
+
 impl Miner {
     pub async fn send_and_confirm(
         &self,
@@ -33,13 +48,29 @@ impl Miner {
         dynamic_cus: bool,
         skip_confirm: bool,
     ) -> ClientResult<Signature> {
+        self.send_and_confirm_receipt(ixs, dynamic_cus, skip_confirm)
+            .await
+            .map(|receipt| receipt.signature)
+    }
+
+    /// Like `send_and_confirm`, but also returns the fee and compute units of
+    /// the transaction.
+    pub async fn send_and_confirm_receipt(
+        &self,
+        ixs: &[Instruction],
+        dynamic_cus: bool,
+        skip_confirm: bool,
+    ) -> ClientResult<TxReceipt> {
+        if self.dry_run {
+            return self.simulate(ixs).await;
+        }
         let mut stdout = stdout();
         let signer = self.signer();
         let client = self.rpc_client.clone();
 
         // Return error if balance is zero
         let balance = client.get_balance(&signer.pubkey()).await.unwrap();
-        if balance <= 0 {
+        if balance == 0 {
             return Err(ClientError {
                 request: None,
                 kind: ClientErrorKind::Custom("Insufficient SOL balance".into()),
@@ -47,22 +78,19 @@ impl Miner {
         }
 
         // Build tx
-        let (hash, slot) = client
-            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
-            .await
-            .unwrap();
         let send_cfg = RpcSendTransactionConfig {
             skip_preflight: false,
             preflight_commitment: Some(CommitmentLevel::Finalized),
             encoding: Some(UiTransactionEncoding::Base64),
             max_retries: Some(RPC_RETRIES),
-            min_context_slot: Some(slot),
+            min_context_slot: None,
         };
-        let mut tx = Transaction::new_with_payer(ixs, Some(&signer.pubkey()));
+        let mut final_ixs = ixs.to_vec();
+        let mut tx = Transaction::new_with_payer(&final_ixs, Some(&signer.pubkey()));
 
         // Simulate tx
         let mut sim_attempts = 0;
-        'simulate: loop {
+        let compute_units = 'simulate: loop {
             let sim_res = client
                 .simulate_transaction_with_config(
                     &tx,
@@ -72,7 +100,7 @@ impl Miner {
                         commitment: Some(self.rpc_client.commitment()),
                         encoding: Some(UiTransactionEncoding::Base64),
                         accounts: None,
-                        min_context_slot: Some(slot),
+                        min_context_slot: None,
                         inner_instructions: false,
                     },
                 )
@@ -80,109 +108,270 @@ impl Miner {
             match sim_res {
                 Ok(sim_res) => {
                     if let Some(err) = sim_res.value.err {
-                        println!("Simulaton error: {:?}", err);
+                        self.error(format!("Simulaton error: {:?}", err));
                         sim_attempts += 1;
                     } else if let Some(units_consumed) = sim_res.value.units_consumed {
                         if dynamic_cus {
-                            println!("Dynamic CUs: {:?}", units_consumed);
+                            self.log(format!("Dynamic CUs: {:?}", units_consumed));
                             let cu_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(
                                 units_consumed as u32 + 1000,
                             );
-                            let cu_price_ix =
-                                ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee);
-                            let mut final_ixs = vec![];
-                            final_ixs.extend_from_slice(&[cu_budget_ix, cu_price_ix]);
+                            let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+                                self.priority_fee(ixs).await,
+                            );
+                            final_ixs = vec![cu_budget_ix, cu_price_ix];
                             final_ixs.extend_from_slice(ixs);
                             tx = Transaction::new_with_payer(&final_ixs, Some(&signer.pubkey()));
                         }
-                        break 'simulate;
+                        break 'simulate Some(units_consumed);
                     }
                 }
                 Err(err) => {
-                    println!("Simulaton error: {:?}", err);
+                    self.error(format!("Simulaton error: {:?}", err));
                     sim_attempts += 1;
                 }
             }
 
             // Abort if sim fails
             if sim_attempts.gt(&SIMULATION_RETRIES) {
+                self.stats.record_failed();
                 return Err(ClientError {
                     request: None,
                     kind: ClientErrorKind::Custom("Simulation failed".into()),
                 });
             }
-        }
+        };
 
         // Submit tx
+        let deadline = Instant::now() + self.send_timeout;
+        let base_price = compute_unit_price(&final_ixs).unwrap_or(0);
+        let mut price = base_price;
+        let (mut hash, mut last_valid_block_height) = loop {
+            match client
+                .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
+                .await
+            {
+                Ok(blockhash) => break blockhash,
+                Err(err) => {
+                    self.error(format!("{:?}", err.kind().to_string()));
+                    if self.is_shutting_down() || Instant::now() >= deadline {
+                        self.stats.record_failed();
+                        return Err(err);
+                    }
+                    tokio::time::sleep(Duration::from_millis(GATEWAY_DELAY)).await;
+                }
+            }
+        };
         tx.sign(&[&signer], hash);
-        // let mut sigs = vec![];
+        // Earlier attempts at a lower price may still land, so all are confirmed
+        let mut sigs: Vec<(Signature, u64)> = vec![];
         let mut attempts = 0;
         loop {
-            println!("Attempt: {:?}", attempts);
-            match client.send_transaction_with_config(&tx, send_cfg).await {
+            // Once shutting down, send nothing new and only wait for earlier attempts
+            if self.is_shutting_down() {
+                let signatures: Vec<Signature> = sigs.iter().map(|(s, _)| *s).collect();
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if !signatures.is_empty() {
+                    if let Some((i, status)) = self.confirm(&signatures, remaining).await {
+                        let (sig, fee) = sigs[i];
+                        return self.landed(sig, fee, status, compute_units).await;
+                    }
+                }
+                self.stats.record_failed();
+                return Err(ClientError {
+                    request: None,
+                    kind: ClientErrorKind::Custom("Shutting down".into()),
+                });
+            }
+
+            // Re-sign the tx with a fresh blockhash once its blockhash expires
+            let mut resign = false;
+            if let Ok(block_height) = client.get_block_height().await {
+                if block_height > last_valid_block_height {
+                    match client
+                        .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
+                        .await
+                    {
+                        Ok((new_hash, new_last_valid_block_height)) => {
+                            self.log(format!(
+                                "Blockhash expired at block height {}, re-signing with {}",
+                                last_valid_block_height, new_hash
+                            ));
+                            hash = new_hash;
+                            last_valid_block_height = new_last_valid_block_height;
+                            resign = true;
+                        }
+                        Err(err) => {
+                            self.error(format!("{:?}", err.kind().to_string()));
+                        }
+                    }
+                }
+            }
+
+            // Reprice the tx if the fee escalates on this attempt
+            if let Some(escalation) = self.fee_escalation {
+                let escalated = escalation.fee(base_price, attempts);
+                if escalated != price {
+                    price = escalated;
+                    final_ixs = with_compute_unit_price(&final_ixs, price);
+                    resign = true;
+                }
+            }
+            if resign {
+                tx = Transaction::new_with_payer(&final_ixs, Some(&signer.pubkey()));
+                tx.sign(&[&signer], hash);
+            }
+            let fee = transaction_fee(&tx);
+            self.log(format!(
+                "Attempt: {} (priority fee: {} microlamports, {} SOL)",
+                attempts,
+                price,
+                lamports_to_sol(fee)
+            ));
+            match self.broadcast(&tx, send_cfg).await {
                 Ok(sig) => {
-                    println!("{:?}", sig);
-                    // sigs.push(sig);
+                    self.log(format!("{:?}", sig));
+                    self.emit(Event::TxSent {
+                        signature: sig.to_string(),
+                        attempt: attempts,
+                        compute_unit_price: price,
+                        fee,
+                    });
+                    if !sigs.iter().any(|(s, _)| *s == sig) {
+                        sigs.push((sig, fee));
+                    }
 
                     // Confirm tx
                     if skip_confirm {
-                        return Ok(sig);
+                        return Ok(TxReceipt {
+                            signature: sig,
+                            fee,
+                            compute_units,
+                        });
                     }
-                    for _ in 0..CONFIRM_RETRIES {
-                        std::thread::sleep(Duration::from_millis(CONFIRM_DELAY));
-                        match client.get_signature_statuses(&[sig]).await {
-                            Ok(signature_statuses) => {
-                                println!("Confirmation: {:?}", signature_statuses.value[0]);
-                                for signature_status in signature_statuses.value {
-                                    if let Some(signature_status) = signature_status.as_ref() {
-                                        if signature_status.confirmation_status.is_some() {
-                                            let current_commitment = signature_status
-                                                .confirmation_status
-                                                .as_ref()
-                                                .unwrap();
-                                            match current_commitment {
-                                                TransactionConfirmationStatus::Processed => {}
-                                                TransactionConfirmationStatus::Confirmed
-                                                | TransactionConfirmationStatus::Finalized => {
-                                                    println!("Transaction landed!");
-                                                    std::thread::sleep(Duration::from_millis(
-                                                        GATEWAY_DELAY,
-                                                    ));
-                                                    return Ok(sig);
-                                                }
-                                            }
-                                        } else {
-                                            println!("No status");
-                                        }
-                                    }
-                                }
-                            }
-
-                            // Handle confirmation errors
-                            Err(err) => {
-                                println!("{:?}", err.kind().to_string());
-                            }
-                        }
+                    let signatures: Vec<Signature> = sigs.iter().map(|(s, _)| *s).collect();
+                    if let Some((i, status)) = self
+                        .confirm(&signatures, Duration::from_millis(CONFIRM_TIMEOUT))
+                        .await
+                    {
+                        let (sig, fee) = sigs[i];
+                        return self.landed(sig, fee, status, compute_units).await;
                     }
-                    println!("Transaction did not land");
+                    self.error("Transaction did not land");
                 }
 
-                // Handle submit errors
+                // Handle submit errors. A failed preflight fails again on every
+                // retry, so the caller must check what changed, unless an earlier
+                // attempt is what changed it.
                 Err(err) => {
-                    println!("{:?}", err.kind().to_string());
+                    self.error(format!("{:?}", err.kind().to_string()));
+                    if is_preflight_failure(&err) {
+                        let signatures: Vec<Signature> = sigs.iter().map(|(s, _)| *s).collect();
+                        if !signatures.is_empty() {
+                            if let Some((i, status)) = self.poll_statuses(&signatures).await {
+                                let (sig, fee) = sigs[i];
+                                return self.landed(sig, fee, status, compute_units).await;
+                            }
+                        }
+                        self.stats.record_failed();
+                        return Err(err);
+                    }
                 }
             }
 
             // Retry
             stdout.flush().ok();
-            std::thread::sleep(Duration::from_millis(GATEWAY_DELAY));
+            tokio::time::sleep(Duration::from_millis(GATEWAY_DELAY)).await;
             attempts += 1;
-            if attempts > GATEWAY_RETRIES {
+            if Instant::now() >= deadline {
+                self.stats.record_failed();
                 return Err(ClientError {
                     request: None,
-                    kind: ClientErrorKind::Custom("Max retries".into()),
+                    kind: ClientErrorKind::Custom("Send deadline exceeded".into()),
                 });
             }
         }
     }
+
+    /// Reports a confirmed transaction and returns its receipt, or the error it
+    /// failed with on chain.
+    async fn landed(
+        &self,
+        signature: Signature,
+        fee: u64,
+        status: Result<TransactionConfirmationStatus, TransactionError>,
+        compute_units: Option<u64>,
+    ) -> ClientResult<TxReceipt> {
+        let status = match status {
+            Ok(status) => status,
+            Err(err) => {
+                self.error(format!("Transaction {} failed: {}", signature, err));
+                self.stats.record_reverted(fee);
+                return Err(ClientError {
+                    request: None,
+                    kind: ClientErrorKind::TransactionError(err),
+                });
+            }
+        };
+        self.log("Transaction landed!");
+        self.emit(Event::TxConfirmed {
+            signature: signature.to_string(),
+            status: format!("{:?}", status).to_lowercase(),
+        });
+        self.stats.record_landed(fee);
+        tokio::time::sleep(Duration::from_millis(GATEWAY_DELAY)).await;
+        Ok(TxReceipt {
+            signature,
+            fee,
+            compute_units,
+        })
+    }
+
+    /// Simulates a transaction instead of sending it, for `--dry-run`. Returns a
+    /// default signature if the simulation succeeds.
+    async fn simulate(&self, ixs: &[Instruction]) -> ClientResult<TxReceipt> {
+        let signer = self.signer();
+        let tx = Transaction::new_with_payer(ixs, Some(&signer.pubkey()));
+        let sim_res = self
+            .rpc_client
+            .simulate_transaction_with_config(
+                &tx,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.rpc_client.commitment()),
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    accounts: None,
+                    min_context_slot: None,
+                    inner_instructions: false,
+                },
+            )
+            .await?;
+        if let Some(err) = sim_res.value.err {
+            return Err(ClientError {
+                request: None,
+                kind: ClientErrorKind::Custom(format!("Simulation failed: {}", err)),
+            });
+        }
+        self.log(format!(
+            "Simulated transaction ({} CUs), not sent",
+            sim_res.value.units_consumed.unwrap_or_default()
+        ));
+        Ok(TxReceipt {
+            signature: Signature::default(),
+            fee: transaction_fee(&tx),
+            compute_units: sim_res.value.units_consumed,
+        })
+    }
+}
+
+/// Returns true if the endpoint rejected a transaction because simulating it failed.
+fn is_preflight_failure(err: &ClientError) -> bool {
+    matches!(
+        err.kind(),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(_),
+            ..
+        })
+    )
 }
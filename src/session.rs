@@ -0,0 +1,213 @@
+use std::{
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+use solana_program::{native_token::lamports_to_sol, pubkey::Pubkey};
+use solana_sdk::{compute_budget, transaction::Transaction};
+use spl_token::amount_to_ui_amount;
+
+use crate::{
+    progress::{format_count, format_duration},
+    shares::save_json,
+};
+
+// Lamports charged per signature
+const BASE_FEE_PER_SIGNATURE: u64 = 5000;
+
+// Compute units a transaction may use per instruction if it sets no limit
+const DEFAULT_CU_LIMIT_PER_IX: u64 = 200_000;
+const MAX_CU_LIMIT: u64 = 1_400_000;
+
+/// Running totals for one authority's mining session.
+#[derive(Default)]
+pub struct SessionStats {
+    pub hashes: Arc<AtomicU64>,
+    pub solutions: AtomicU64,
+    pub landed: AtomicU64,
+
+    /// Rewards for the landed solutions at the reward rate they were mined at
+    pub earned: AtomicU64,
+
+    /// Transactions of any kind that were confirmed, or given up on
+    pub transactions_landed: AtomicU64,
+    pub transactions_failed: AtomicU64,
+
+    /// Lamports paid in fees by the landed transactions
+    pub fees: AtomicU64,
+}
+
+impl SessionStats {
+    /// Counts a confirmed transaction and the lamports it paid in fees.
+    pub fn record_landed(&self, fee: u64) {
+        self.transactions_landed.fetch_add(1, Ordering::Relaxed);
+        self.fees.fetch_add(fee, Ordering::Relaxed);
+    }
+
+    pub fn record_failed(&self) {
+        self.transactions_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a transaction that was included but failed, which still paid its fees.
+    pub fn record_reverted(&self, fee: u64) {
+        self.transactions_failed.fetch_add(1, Ordering::Relaxed);
+        self.fees.fetch_add(fee, Ordering::Relaxed);
+    }
+}
+
+/// What a mining session achieved, printed on exit and optionally saved as JSON.
+#[derive(Serialize)]
+pub struct SessionReport {
+    pub duration_secs: u64,
+    pub dry_run: bool,
+    #[serde(flatten)]
+    pub total: AuthorityReport,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authorities: Vec<AuthorityReport>,
+}
+
+#[derive(Serialize)]
+pub struct AuthorityReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authority: Option<String>,
+    pub solutions: u64,
+    pub transactions_landed: u64,
+    pub transactions_failed: u64,
+    pub fees_sol: f64,
+    pub ore_earned: f64,
+    pub average_hash_rate: f64,
+}
+
+impl SessionReport {
+    /// Summarizes the sessions of `authorities`. Per-authority figures are only
+    /// included if there is more than one.
+    pub fn new(
+        authorities: &[(Pubkey, Arc<SessionStats>)],
+        duration: Duration,
+        dry_run: bool,
+    ) -> Self {
+        let secs = duration.as_secs_f64().max(1.0);
+        let report = |authority: Option<Pubkey>, stats: &[&SessionStats]| {
+            let sum = |field: fn(&SessionStats) -> &AtomicU64| -> u64 {
+                stats.iter().map(|s| field(s).load(Ordering::Relaxed)).sum()
+            };
+            AuthorityReport {
+                authority: authority.map(|a| a.to_string()),
+                solutions: sum(|s| &s.solutions),
+                transactions_landed: sum(|s| &s.transactions_landed),
+                transactions_failed: sum(|s| &s.transactions_failed),
+                fees_sol: lamports_to_sol(sum(|s| &s.fees)),
+                ore_earned: amount_to_ui_amount(sum(|s| &s.earned), ore::TOKEN_DECIMALS),
+                average_hash_rate: sum(|s| &s.hashes) as f64 / secs,
+            }
+        };
+        let all: Vec<&SessionStats> = authorities.iter().map(|(_, s)| s.as_ref()).collect();
+        Self {
+            duration_secs: duration.as_secs(),
+            dry_run,
+            total: report(None, &all),
+            authorities: if authorities.len() > 1 {
+                authorities
+                    .iter()
+                    .map(|(authority, stats)| report(Some(*authority), &[stats]))
+                    .collect()
+            } else {
+                vec![]
+            },
+        }
+    }
+
+    pub fn print(&self) {
+        let total = &self.total;
+        println!("\nSession report");
+        println!(
+            "  Duration: {}",
+            format_duration(Duration::from_secs(self.duration_secs))
+        );
+        println!("  Solutions: {}", total.solutions);
+        println!(
+            "  Transactions: {} landed, {} failed",
+            total.transactions_landed, total.transactions_failed
+        );
+        println!("  Fees paid: {} SOL", total.fees_sol);
+        println!(
+            "  ORE {}: {}",
+            if self.dry_run {
+                "that would have been earned"
+            } else {
+                "earned"
+            },
+            total.ore_earned
+        );
+        println!(
+            "  Average hash rate: {}H/s",
+            format_count(total.average_hash_rate)
+        );
+        for authority in &self.authorities {
+            println!(
+                "  [{}] {} solutions, {} landed, {} failed, {} ORE, {}H/s",
+                authority.authority.as_deref().unwrap_or_default(),
+                authority.solutions,
+                authority.transactions_landed,
+                authority.transactions_failed,
+                authority.ore_earned,
+                format_count(authority.average_hash_rate),
+            );
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        save_json(path, self)
+    }
+}
+
+/// The fee a transaction pays: the base fee per signature plus its compute unit
+/// price times its compute unit limit.
+pub fn transaction_fee(tx: &Transaction) -> u64 {
+    let message = &tx.message;
+    let mut cu_limit = None;
+    let mut cu_price = 0u64;
+    let mut ix_count = 0u64;
+    for ix in &message.instructions {
+        let program_id = message.account_keys[ix.program_id_index as usize];
+        if program_id != compute_budget::id() {
+            ix_count += 1;
+            continue;
+        }
+        // SetComputeUnitLimit(u32) and SetComputeUnitPrice(u64)
+        match ix.data.split_first() {
+            Some((2, data)) if data.len() >= 4 => {
+                cu_limit = Some(u32::from_le_bytes(data[..4].try_into().unwrap()) as u64);
+            }
+            Some((3, data)) if data.len() >= 8 => {
+                cu_price = u64::from_le_bytes(data[..8].try_into().unwrap());
+            }
+            _ => {}
+        }
+    }
+    let cu_limit =
+        cu_limit.unwrap_or_else(|| (ix_count * DEFAULT_CU_LIMIT_PER_IX).min(MAX_CU_LIMIT));
+    let priority_fee = (cu_price as u128 * cu_limit as u128).div_ceil(1_000_000) as u64;
+    BASE_FEE_PER_SIGNATURE * message.header.num_required_signatures as u64 + priority_fee
+}
+
+/// Resolves once the process receives SIGINT, or SIGTERM on Unix.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.ok();
+}
@@ -0,0 +1,216 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{compute_budget::ComputeBudgetInstruction, signature::Signer};
+use spl_token::amount_to_ui_amount;
+
+use crate::{cu_limits::CU_LIMIT_CLAIM, utils::get_proof, Miner};
+
+// How often the share ledger is written to disk while the pool is serving
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+// Number of members paid in a single transaction
+const PAYOUTS_PER_TX: usize = 4;
+
+/// Work credited to each pool member, keyed by wallet address. Only the pool
+/// server writes this file.
+#[derive(Serialize, Deserialize, Default)]
+pub struct ShareLedger {
+    pub members: BTreeMap<String, MemberShares>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct MemberShares {
+    /// Number of accepted shares
+    pub shares: u64,
+
+    /// Expected hashes behind the accepted shares, so shares found at different
+    /// share difficulties are weighted fairly
+    pub work: f64,
+}
+
+impl ShareLedger {
+    pub fn credit(&mut self, member: Pubkey, work: f64) {
+        let entry = self.members.entry(member.to_string()).or_default();
+        entry.shares += 1;
+        entry.work += work;
+    }
+}
+
+/// What each member has been paid so far, keyed by wallet address. Only
+/// `ore pool payout` writes this file, so it never races the server.
+#[derive(Serialize, Deserialize, Default)]
+struct PayoutLedger {
+    members: BTreeMap<String, MemberPayout>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct MemberPayout {
+    /// Work that has already been paid for
+    paid_work: f64,
+
+    /// ORE paid, in grains
+    paid: u64,
+}
+
+impl Miner {
+    pub fn pool_dir(&self, authority: Pubkey) -> PathBuf {
+        self.data_dir.join("pool").join(authority.to_string())
+    }
+
+    /// Claims the pool's rewards and splits its ORE balance between members in
+    /// proportion to the work they have not been paid for yet.
+    pub async fn pool_payout(&self) {
+        let signer = self.signer();
+        let dir = self.pool_dir(signer.pubkey());
+        let shares: ShareLedger = load_json(&dir.join("shares.json"));
+        let payouts_path = dir.join("payouts.json");
+        let mut payouts: PayoutLedger = load_json(&payouts_path);
+
+        // Find the work each member is owed for
+        let unpaid: Vec<(Pubkey, f64)> = shares
+            .members
+            .iter()
+            .filter_map(|(member, entry)| {
+                let paid = payouts.members.get(member).map_or(0.0, |p| p.paid_work);
+                let member = Pubkey::from_str(member).ok()?;
+                (entry.work > paid).then_some((member, entry.work - paid))
+            })
+            .collect();
+        let total_work: f64 = unpaid.iter().map(|(_, work)| work).sum();
+        if unpaid.is_empty() {
+            println!("No unpaid shares");
+            return;
+        }
+
+        // Claim rewards into the pool's token account
+        let token_account = self.initialize_ata().await;
+        let proof = get_proof(&self.rpc_client, signer.pubkey()).await;
+        if proof.claimable_rewards.gt(&0) {
+            println!(
+                "Claiming {} ORE...",
+                amount_to_ui_amount(proof.claimable_rewards, ore::TOKEN_DECIMALS)
+            );
+            let ix =
+                ore::instruction::claim(signer.pubkey(), token_account, proof.claimable_rewards);
+            let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_CLAIM);
+            let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+                self.priority_fee(std::slice::from_ref(&ix)).await,
+            );
+            if let Err(err) = self
+                .send_and_confirm(&[cu_limit_ix, cu_price_ix, ix], false, false)
+                .await
+            {
+                println!("Error: {:?}", err);
+                return;
+            }
+        }
+        let balance = self
+            .rpc_client
+            .get_token_account_balance(&token_account)
+            .await
+            .expect("Failed to fetch pool token balance");
+        let balance: u64 = balance.amount.parse().unwrap_or(0);
+        if balance.eq(&0) {
+            println!("Nothing to pay out");
+            return;
+        }
+
+        // Pay members a few at a time, recording each batch once it lands
+        println!(
+            "Paying {} ORE to {} members",
+            amount_to_ui_amount(balance, ore::TOKEN_DECIMALS),
+            unpaid.len()
+        );
+        for batch in unpaid.chunks(PAYOUTS_PER_TX) {
+            let mut ixs = vec![];
+            let mut amounts = vec![];
+            for (member, work) in batch {
+                let amount = (balance as f64 * work / total_work) as u64;
+                let member_account = spl_associated_token_account::get_associated_token_address(
+                    member,
+                    &ore::MINT_ADDRESS,
+                );
+                ixs.push(
+                    spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                        &signer.pubkey(),
+                        member,
+                        &ore::MINT_ADDRESS,
+                        &spl_token::id(),
+                    ),
+                );
+                ixs.push(
+                    spl_token::instruction::transfer(
+                        &spl_token::id(),
+                        &token_account,
+                        &member_account,
+                        &signer.pubkey(),
+                        &[],
+                        amount,
+                    )
+                    .unwrap(),
+                );
+                amounts.push(amount);
+            }
+            match self.send_and_confirm(&ixs, true, false).await {
+                Ok(sig) => {
+                    for ((member, work), amount) in batch.iter().zip(amounts) {
+                        println!(
+                            "  {}: {} ORE",
+                            member,
+                            amount_to_ui_amount(amount, ore::TOKEN_DECIMALS)
+                        );
+                        let entry = payouts.members.entry(member.to_string()).or_default();
+                        entry.paid_work += work;
+                        entry.paid += amount;
+                    }
+                    println!("{}", sig);
+                    save_json(&payouts_path, &payouts).expect("Failed to save payouts");
+                }
+                Err(err) => {
+                    println!("Error: {:?}", err);
+                    println!("Remaining members will be paid on the next payout");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Reads a ledger, or returns an empty one if it does not exist yet.
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> T {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data)
+            .unwrap_or_else(|err| panic!("Failed to parse {}: {}", path.display(), err)),
+        Err(_) => T::default(),
+    }
+}
+
+/// Writes a ledger, replacing the previous version atomically.
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+    fs::rename(tmp, path)
+}
+
+/// Periodically saves the share ledger until the task is aborted.
+pub async fn save_shares(path: PathBuf, ledger: Arc<Mutex<ShareLedger>>) {
+    loop {
+        tokio::time::sleep(SAVE_INTERVAL).await;
+        let result = save_json(&path, &*ledger.lock().unwrap());
+        if let Err(err) = result {
+            println!("Failed to save share ledger: {}", err);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    fs,
+    io::{stdin, Read},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64},
+        Arc,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{keccak::Hash as KeccakHash, signature::Signer};
+
+use crate::{
+    backend::HashBackend,
+    progress::report_progress,
+    utils::{get_proof, get_treasury},
+    workers::{nonce_ranges, WorkerOptions},
+    Miner,
+};
+
+/// A solved challenge, as printed by `ore solve` and read by `ore submit-solution`.
+#[derive(Serialize, Deserialize)]
+pub struct Solution {
+    pub authority: String,
+    pub challenge: String,
+    pub difficulty: String,
+    pub nonce: u64,
+    pub hash: String,
+}
+
+impl Miner {
+    /// Searches for a solution without touching the network and prints it as JSON.
+    pub async fn solve(
+        &self,
+        challenge: KeccakHash,
+        authority: Pubkey,
+        difficulty: KeccakHash,
+        threads: u64,
+        backend: Arc<dyn HashBackend>,
+    ) {
+        let pool = self.worker_pool(&backend, threads, WorkerOptions::default());
+        let hashes = Arc::new(AtomicU64::new(0));
+        let progress = tokio::spawn(report_progress(hashes.clone(), 0, difficulty));
+        let solution = tokio::task::block_in_place(|| {
+            pool.search(
+                challenge,
+                authority,
+                difficulty,
+                Arc::new(nonce_ranges(threads)),
+                Arc::new(AtomicBool::new(false)),
+                hashes,
+            )
+        });
+        progress.abort();
+        eprintln!();
+
+        let (hash, nonce) = solution.expect("Search stopped without a solution");
+        let solution = Solution {
+            authority: authority.to_string(),
+            challenge: hex::encode(challenge),
+            difficulty: hex::encode(difficulty),
+            nonce,
+            hash: hex::encode(hash),
+        };
+        println!("{}", serde_json::to_string_pretty(&solution).unwrap());
+    }
+
+    /// Checks a solution from `ore solve` against the current on-chain state and
+    /// submits it. Reads the solution from stdin if no path is given.
+    pub async fn submit_solution(&self, path: Option<String>) {
+        let data = match path.as_deref() {
+            Some("-") | None => {
+                let mut data = vec![];
+                stdin().read_to_end(&mut data).map(|_| data)
+            }
+            Some(path) => fs::read(path),
+        };
+        let data = match data {
+            Ok(data) => data,
+            Err(err) => {
+                println!("Failed to read solution: {}", err);
+                return;
+            }
+        };
+        let solution: Solution = match serde_json::from_slice(&data) {
+            Ok(solution) => solution,
+            Err(err) => {
+                println!("Invalid solution: {}", err);
+                return;
+            }
+        };
+        let (Ok(authority), Ok(hash)) = (
+            Pubkey::from_str(&solution.authority),
+            parse_hex_hash(&solution.hash),
+        ) else {
+            println!("Invalid solution: malformed authority or hash");
+            return;
+        };
+
+        // The mine instruction must be signed by the proof's authority
+        let signer = self.signer();
+        if signer.pubkey().ne(&authority) {
+            println!(
+                "Solution is for {}, but the keypair is {}",
+                authority,
+                signer.pubkey()
+            );
+            return;
+        }
+
+        // Make sure the solution still solves the live challenge
+        let treasury = get_treasury(&self.rpc_client).await;
+        let proof = get_proof(&self.rpc_client, authority).await;
+        if !self.validate_hash(
+            hash,
+            proof.hash.into(),
+            authority,
+            solution.nonce,
+            treasury.difficulty.into(),
+        ) {
+            println!("Solution is not valid for the current challenge and difficulty");
+            return;
+        }
+
+        println!("Submitting hash for validation...");
+        let Some(bus) = self.find_bus_id(treasury.reward_rate).await else {
+            println!("All busses are exhausted until the next epoch, try again after the reset");
+            return;
+        };
+        match self.submit_hash(hash, solution.nonce, &bus).await {
+            Ok(receipt) => println!("Success: {}", receipt.signature),
+            Err(err) => println!("Error: {:?}", err),
+        }
+    }
+}
+
+/// Parses a 32 byte hash from a hex string.
+pub fn parse_hex_hash(s: &str) -> Result<KeccakHash, String> {
+    let bytes = hex::decode(s.trim_start_matches("0x")).map_err(|err| err.to_string())?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| "expected 32 bytes".to_string())?;
+    Ok(KeccakHash::new_from_array(bytes))
+}
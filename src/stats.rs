@@ -0,0 +1,216 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use solana_program::native_token::lamports_to_sol;
+use spl_token::amount_to_ui_amount;
+
+use crate::{progress::format_count, Miner, OutputFormat, StatsPeriod};
+
+/// What happened to a solution after it was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmitResult {
+    /// The mine transaction landed
+    Landed,
+
+    /// The challenge moved on before the transaction landed
+    Stale,
+
+    /// The transaction could not be landed
+    Failed,
+}
+
+/// One solved challenge, as kept in the stats store.
+#[derive(Serialize, Deserialize)]
+pub struct SolveRecord {
+    /// Unix time the solution was found at
+    pub time: i64,
+    pub authority: String,
+    pub challenge: String,
+    pub nonce: u64,
+    pub hashes: u64,
+    pub solve_secs: f64,
+    pub submit_secs: f64,
+    pub bus: Option<u64>,
+
+    /// Lamports paid in fees by the mine transaction
+    pub fee: u64,
+    pub compute_units: Option<u64>,
+    pub result: SubmitResult,
+
+    /// Change in the proof's claimable rewards from landing the solution
+    pub reward: u64,
+}
+
+/// Totals for one day or week of records.
+#[derive(Default, Serialize)]
+struct PeriodStats {
+    period: String,
+    solutions: u64,
+    landed: u64,
+    landing_rate: f64,
+    hashes: u64,
+    mining_hours: f64,
+    hash_rate: f64,
+
+    /// Relative change in hash rate from the previous period
+    hash_rate_change: Option<f64>,
+    ore_earned: f64,
+    ore_per_hour: f64,
+    fees_sol: f64,
+    sol_per_ore: Option<f64>,
+
+    #[serde(skip)]
+    solve_secs: f64,
+    #[serde(skip)]
+    reward: u64,
+    #[serde(skip)]
+    fees: u64,
+}
+
+impl PeriodStats {
+    fn add(&mut self, record: &SolveRecord) {
+        self.solutions += 1;
+        if record.result == SubmitResult::Landed {
+            self.landed += 1;
+        }
+        self.hashes += record.hashes;
+        self.solve_secs += record.solve_secs;
+        self.mining_hours += (record.solve_secs + record.submit_secs) / 3600.0;
+        self.reward += record.reward;
+        self.fees += record.fee;
+    }
+
+    fn finish(&mut self, previous_hash_rate: Option<f64>) {
+        self.landing_rate = self.landed as f64 / self.solutions.max(1) as f64;
+        self.hash_rate = self.hashes as f64 / self.solve_secs.max(f64::EPSILON);
+        self.hash_rate_change = previous_hash_rate
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| self.hash_rate / rate - 1.0);
+        self.ore_earned = amount_to_ui_amount(self.reward, ore::TOKEN_DECIMALS);
+        self.ore_per_hour = self.ore_earned / self.mining_hours.max(f64::EPSILON);
+        self.fees_sol = lamports_to_sol(self.fees);
+        self.sol_per_ore = (self.ore_earned > 0.0).then(|| self.fees_sol / self.ore_earned);
+    }
+}
+
+#[derive(Serialize)]
+struct StatsReport {
+    periods: Vec<PeriodStats>,
+    total: PeriodStats,
+}
+
+impl Miner {
+    /// The file every solution found by `ore mine` is appended to.
+    pub fn stats_path(&self) -> PathBuf {
+        self.data_dir.join("stats.jsonl")
+    }
+
+    /// Appends a solution to the stats store.
+    pub fn record_solve(&self, record: &SolveRecord) {
+        let path = self.stats_path();
+        let result = fs::create_dir_all(&self.data_dir).and_then(|_| {
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            let mut line = serde_json::to_vec(record)?;
+            line.push(b'\n');
+            file.write_all(&line)
+        });
+        if let Err(err) = result {
+            self.error(format!(
+                "Failed to record solution in {}: {}",
+                path.display(),
+                err
+            ));
+        }
+    }
+
+    /// Summarizes the stats store by day or week.
+    pub fn stats(&self, period: StatsPeriod, format: OutputFormat) {
+        let path = self.stats_path();
+        let data = fs::read_to_string(&path).unwrap_or_default();
+        let records: Vec<SolveRecord> = data
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+
+        let mut periods: BTreeMap<String, PeriodStats> = BTreeMap::new();
+        let mut total = PeriodStats {
+            period: "total".to_string(),
+            ..Default::default()
+        };
+        for record in &records {
+            let Some(time) = Local.timestamp_opt(record.time, 0).single() else {
+                continue;
+            };
+            let key = match period {
+                StatsPeriod::Day => time.format("%Y-%m-%d").to_string(),
+                StatsPeriod::Week => time.format("%G-W%V").to_string(),
+            };
+            periods
+                .entry(key.clone())
+                .or_insert_with(|| PeriodStats {
+                    period: key,
+                    ..Default::default()
+                })
+                .add(record);
+            total.add(record);
+        }
+        let mut previous = None;
+        let periods: Vec<PeriodStats> = periods
+            .into_values()
+            .map(|mut stats| {
+                stats.finish(previous);
+                previous = Some(stats.hash_rate);
+                stats
+            })
+            .collect();
+        total.finish(None);
+        let report = StatsReport { periods, total };
+
+        match format {
+            OutputFormat::Table => {
+                if records.is_empty() {
+                    println!("No solutions recorded in {}", path.display());
+                    return;
+                }
+                println!(
+                    "{:>10}  {:>9}  {:>7}  {:>12}  {:>8}  {:>12}  {:>10}  {:>10}",
+                    "Period",
+                    "Solutions",
+                    "Landed",
+                    "Hash rate",
+                    "Trend",
+                    "ORE",
+                    "ORE/hour",
+                    "SOL/ORE"
+                );
+                for stats in report.periods.iter().chain([&report.total]) {
+                    println!(
+                        "{:>10}  {:>9}  {:>6.1}%  {:>10}H/s  {:>8}  {:>12.4}  {:>10.4}  {:>10}",
+                        stats.period,
+                        stats.solutions,
+                        stats.landing_rate * 100.0,
+                        format_count(stats.hash_rate),
+                        stats
+                            .hash_rate_change
+                            .map_or("-".to_string(), |c| format!("{:+.1}%", c * 100.0)),
+                        stats.ore_earned,
+                        stats.ore_per_hour,
+                        stats
+                            .sol_per_ore
+                            .map_or("-".to_string(), |c| format!("{:.6}", c)),
+                    );
+                }
+            }
+            OutputFormat::Json => {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            }
+        }
+    }
+}
@@ -12,7 +12,7 @@ impl Miner {
             let balance = treasury_tokens.token_amount.ui_amount_string;
             println!("{:} ORE", balance);
             println!("Admin: {}", treasury.admin);
-            println!("Difficulty: {}", treasury.difficulty.to_string());
+            println!("Difficulty: {}", treasury.difficulty);
             println!("Last reset at: {}", treasury.last_reset_at);
             println!(
                 "Reward rate: {} ORE",
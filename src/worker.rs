@@ -0,0 +1,212 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use solana_program::pubkey::Pubkey;
+use solana_sdk::keccak::Hash as KeccakHash;
+use tokio::{net::TcpStream, sync::mpsc, task::JoinHandle};
+
+use crate::{
+    backend::HashBackend,
+    progress::report_progress,
+    protocol::{read_message, write_message, LineReader, Message},
+    solve::parse_hex_hash,
+    workers::{split_range, WorkerOptions, WorkerPool},
+    Miner,
+};
+
+// How long to wait before reconnecting to the coordinator
+pub const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// A nonce range to search, as received in a `Work` message.
+pub struct RemoteWork {
+    pub challenge: KeccakHash,
+    pub authority: Pubkey,
+    pub difficulty: KeccakHash,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl RemoteWork {
+    pub fn parse(message: Message) -> Option<Self> {
+        let Message::Work {
+            challenge,
+            authority,
+            difficulty,
+            start,
+            end,
+        } = message
+        else {
+            return None;
+        };
+        Some(Self {
+            challenge: parse_hex_hash(&challenge).ok()?,
+            authority: Pubkey::from_str(&authority).ok()?,
+            difficulty: parse_hex_hash(&difficulty).ok()?,
+            start,
+            end,
+        })
+    }
+}
+
+/// What a remote search reports back, tagged with the id of the search.
+pub enum SearchEvent {
+    Found(KeccakHash, u64),
+    Exhausted,
+}
+
+/// A search running on the worker pool for one piece of remote work.
+pub struct RemoteSearch {
+    cancelled: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+}
+
+impl RemoteSearch {
+    /// Searches `work` with `threads` workers on a blocking thread, sending each hash at or below its
+    /// difficulty to `events`. Stops after the first hash unless `keep_going`
+    /// is set, and reports `Exhausted` once the whole range is searched.
+    pub fn spawn(
+        pool: &Arc<WorkerPool>,
+        work: RemoteWork,
+        id: u64,
+        threads: u64,
+        keep_going: bool,
+        hashes: Arc<AtomicU64>,
+        events: mpsc::UnboundedSender<(u64, SearchEvent)>,
+    ) -> Self {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+        tokio::task::spawn_blocking({
+            let pool = pool.clone();
+            let cancelled = cancelled.clone();
+            let stop = stop.clone();
+            move || {
+                // Each pass resumes where the previous one found a hash
+                let ranges = Arc::new(split_range(work.start, work.end, threads));
+                loop {
+                    let solution = pool.search(
+                        work.challenge,
+                        work.authority,
+                        work.difficulty,
+                        ranges.clone(),
+                        stop.clone(),
+                        hashes.clone(),
+                    );
+                    stop.store(false, Ordering::SeqCst);
+                    if cancelled.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    let event = match solution {
+                        Some((hash, nonce)) => SearchEvent::Found(hash, nonce),
+                        None => SearchEvent::Exhausted,
+                    };
+                    let done = matches!(event, SearchEvent::Exhausted) || !keep_going;
+                    if events.send((id, event)).is_err() || done {
+                        return;
+                    }
+                }
+            }
+        });
+        Self { cancelled, stop }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Miner {
+    /// Searches the nonce ranges handed out by a coordinator and reports back
+    /// any solutions. Reconnects if the coordinator goes away.
+    pub async fn worker(&self, connect: String, threads: u64, backend: Arc<dyn HashBackend>) {
+        let pool = self.worker_pool(&backend, threads, WorkerOptions::default());
+        println!("Hashing with the {} backend", pool.backend().name());
+        loop {
+            match TcpStream::connect(&connect).await {
+                Ok(stream) => {
+                    println!("Connected to coordinator {}", connect);
+                    if let Err(err) = self.serve_coordinator(&pool, stream, threads).await {
+                        println!("\nConnection to coordinator lost: {}", err);
+                    } else {
+                        println!("\nCoordinator closed the connection");
+                    }
+                }
+                Err(err) => println!("Failed to connect to {}: {}", connect, err),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn serve_coordinator(
+        &self,
+        pool: &Arc<WorkerPool>,
+        stream: TcpStream,
+        threads: u64,
+    ) -> std::io::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = LineReader::new(reader);
+        write_message(&mut writer, &Message::Hello { threads }).await?;
+
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        let mut current: Option<(RemoteSearch, KeccakHash, JoinHandle<()>)> = None;
+        let mut search_id = 0u64;
+        let result = loop {
+            tokio::select! {
+                message = read_message(&mut lines) => match message {
+                    Ok(Some(message)) => {
+                        let Some(work) = RemoteWork::parse(message) else {
+                            continue;
+                        };
+                        if let Some((search, _, progress)) = current.take() {
+                            search.cancel();
+                            progress.abort();
+                        }
+                        search_id += 1;
+                        let challenge = work.challenge;
+                        let hashes = Arc::new(AtomicU64::new(0));
+                        let progress = tokio::spawn(report_progress(hashes.clone(), 0, work.difficulty));
+                        let search = RemoteSearch::spawn(pool, work, search_id, threads, false, hashes, event_tx.clone());
+                        current = Some((search, challenge, progress));
+                    }
+                    Ok(None) => break Ok(()),
+                    Err(err) => break Err(err),
+                },
+                Some((id, event)) = event_rx.recv() => {
+                    // Ignore results from searches that were replaced by newer work
+                    if id != search_id {
+                        continue;
+                    }
+                    let Some((_, challenge, progress)) = current.take() else {
+                        continue;
+                    };
+                    progress.abort();
+                    let message = match event {
+                        SearchEvent::Found(hash, nonce) => {
+                            println!("\nFound solution {}", hash);
+                            Message::Solution {
+                                challenge: hex::encode(challenge),
+                                nonce,
+                                hash: hex::encode(hash),
+                            }
+                        }
+                        SearchEvent::Exhausted => Message::Exhausted,
+                    };
+                    if let Err(err) = write_message(&mut writer, &message).await {
+                        break Err(err);
+                    }
+                }
+            }
+        };
+        if let Some((search, _, progress)) = current.take() {
+            search.cancel();
+            progress.abort();
+        }
+        result
+    }
+}
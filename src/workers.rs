@@ -0,0 +1,343 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{channel, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use solana_program::pubkey::Pubkey;
+use solana_sdk::keccak::Hash as KeccakHash;
+
+use crate::{
+    backend::{HashBackend, Midstate},
+    events::log,
+    Miner,
+};
+
+// Number of nonces each worker hashes between checks of the shared stop flag
+const CHECK_INTERVAL: u64 = 10_000;
+
+// How long a worker hashes for one search before moving on to the next queued one
+const TIME_SLICE: Duration = Duration::from_millis(100);
+
+// How often a suspended worker checks whether it may resume
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How the hashing threads are scheduled.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WorkerOptions {
+    /// Pin each worker to its own CPU core
+    pub pin_cores: bool,
+
+    /// Percentage of wall time each worker may spend hashing
+    pub max_cpu: Option<u8>,
+
+    /// Niceness of the worker threads
+    pub nice: Option<i32>,
+}
+
+/// A fixed set of long-lived hashing threads. Each search hands every worker a
+/// disjoint nonce range over a channel; the threads stay parked between
+/// challenges instead of being spawned and joined every round. Concurrent
+/// searches share the workers fairly: each worker takes turns of `TIME_SLICE`
+/// on every search it has been given.
+pub struct WorkerPool {
+    backend: Arc<dyn HashBackend>,
+    workers: Vec<Sender<Job>>,
+    paused: Arc<AtomicBool>,
+    stopped: Arc<AtomicBool>,
+}
+
+struct Job {
+    midstate: Arc<Midstate>,
+    difficulty: KeccakHash,
+    ranges: Arc<Vec<NonceRange>>,
+    worker: usize,
+    found_solution: Arc<AtomicBool>,
+    hashes: Arc<AtomicU64>,
+    done: Sender<Option<(KeccakHash, u64)>>,
+}
+
+impl WorkerPool {
+    /// Spawns `threads` workers scheduled according to `options`. With
+    /// `events`, scheduling problems are reported on stderr.
+    pub fn new(
+        backend: Arc<dyn HashBackend>,
+        threads: u64,
+        options: WorkerOptions,
+        events: bool,
+    ) -> Self {
+        let core_ids = if options.pin_cores {
+            core_affinity::get_core_ids().unwrap_or_default()
+        } else {
+            vec![]
+        };
+        if options.pin_cores && core_ids.is_empty() {
+            log(
+                events,
+                "Failed to read CPU cores, workers will not be pinned",
+            );
+        }
+        let paused = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let workers = (0..threads as usize)
+            .map(|i| {
+                let (tx, rx) = channel::<Job>();
+                let backend = backend.clone();
+                let paused = paused.clone();
+                let stopped = stopped.clone();
+                let core_id = (!core_ids.is_empty()).then(|| core_ids[i % core_ids.len()]);
+                std::thread::spawn(move || {
+                    if let Some(core_id) = core_id {
+                        core_affinity::set_for_current(core_id);
+                    }
+                    if let Some(nice) = options.nice {
+                        set_nice(nice, events);
+                    }
+                    let mut queue = VecDeque::new();
+                    loop {
+                        // Wait for work if there is nothing to do, then pick up any new searches
+                        if queue.is_empty() {
+                            match rx.recv() {
+                                Ok(job) => queue.push_back(job),
+                                Err(_) => break,
+                            }
+                        }
+                        queue.extend(rx.try_iter());
+                        let job = queue.pop_front().unwrap();
+                        match run_job(&backend, &job, options.max_cpu, &paused, &stopped) {
+                            JobStatus::Finished(solution) => {
+                                job.done.send(solution).ok();
+                            }
+                            JobStatus::Yielded => queue.push_back(job),
+                        }
+                    }
+                });
+                tx
+            })
+            .collect();
+        Self {
+            backend,
+            workers,
+            paused,
+            stopped,
+        }
+    }
+
+    pub fn backend(&self) -> &Arc<dyn HashBackend> {
+        &self.backend
+    }
+
+    pub fn size(&self) -> u64 {
+        self.workers.len() as u64
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Suspends or resumes hashing. Suspended workers keep their place in any
+    /// running search and carry on from it once resumed.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Ends every running search, and makes any later search return at once.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Searches for a hash at or below `difficulty` with one worker per range.
+    /// Each worker keeps its range's `next` nonce up to date, so a search can be
+    /// resumed later. Workers stop as soon as one of them finds a solution,
+    /// `found_solution` is set from outside, the pool is stopped, or their range
+    /// is exhausted, and add the number of hashes they computed to `hashes`.
+    pub fn search(
+        &self,
+        hash: KeccakHash,
+        pubkey: Pubkey,
+        difficulty: KeccakHash,
+        ranges: Arc<Vec<NonceRange>>,
+        found_solution: Arc<AtomicBool>,
+        hashes: Arc<AtomicU64>,
+    ) -> Option<(KeccakHash, u64)> {
+        assert!(
+            ranges.len() <= self.workers.len(),
+            "More nonce ranges than workers"
+        );
+        let midstate = Arc::new(Midstate::new(&hash, &pubkey));
+        let (done_tx, done_rx) = channel();
+        for (i, worker) in self.workers.iter().take(ranges.len()).enumerate() {
+            worker
+                .send(Job {
+                    midstate: midstate.clone(),
+                    difficulty,
+                    ranges: ranges.clone(),
+                    worker: i,
+                    found_solution: found_solution.clone(),
+                    hashes: hashes.clone(),
+                    done: done_tx.clone(),
+                })
+                .expect("Worker thread exited");
+        }
+        drop(done_tx);
+
+        let mut solution = None;
+        for result in done_rx.iter() {
+            if result.is_some() {
+                solution = result;
+            }
+        }
+        solution
+    }
+}
+
+/// A span of nonces assigned to one worker, and the next nonce it will try.
+pub struct NonceRange {
+    pub next: AtomicU64,
+    pub end: u64,
+}
+
+/// Splits `start..end` into `parts` disjoint ranges.
+pub fn split_range(start: u64, end: u64, parts: u64) -> Vec<NonceRange> {
+    let size = end.saturating_sub(start).saturating_div(parts);
+    (0..parts)
+        .map(|i| NonceRange {
+            next: AtomicU64::new(start.saturating_add(size.saturating_mul(i))),
+            end: if i + 1 == parts {
+                end
+            } else {
+                start.saturating_add(size.saturating_mul(i + 1))
+            },
+        })
+        .collect()
+}
+
+/// Splits the whole nonce space into `threads` disjoint ranges.
+pub fn nonce_ranges(threads: u64) -> Vec<NonceRange> {
+    split_range(0, u64::MAX, threads)
+}
+
+impl Miner {
+    /// Returns the miner's worker pool, spawning it on first use.
+    pub fn worker_pool(
+        &self,
+        backend: &Arc<dyn HashBackend>,
+        threads: u64,
+        options: WorkerOptions,
+    ) -> Arc<WorkerPool> {
+        self.worker_pool
+            .get_or_init(|| {
+                Arc::new(WorkerPool::new(
+                    backend.clone(),
+                    threads,
+                    options,
+                    self.events.is_some(),
+                ))
+            })
+            .clone()
+    }
+}
+
+enum JobStatus {
+    Finished(Option<(KeccakHash, u64)>),
+    Yielded,
+}
+
+/// Hashes a job's range for one time slice, or until it finishes.
+fn run_job(
+    backend: &Arc<dyn HashBackend>,
+    job: &Job,
+    max_cpu: Option<u8>,
+    paused: &AtomicBool,
+    stopped: &AtomicBool,
+) -> JobStatus {
+    let cancelled =
+        || job.found_solution.load(Ordering::Relaxed) || stopped.load(Ordering::Relaxed);
+    let lanes = backend.lanes() as u64;
+    let check_every = (CHECK_INTERVAL / lanes).max(1);
+    let mut batch = vec![KeccakHash::default(); lanes as usize];
+    let range = &job.ranges[job.worker];
+    let mut nonce = range.next.load(Ordering::Relaxed);
+    let mut batches: u64 = 0;
+    let started = Instant::now();
+    let mut slice_started = started;
+    loop {
+        if nonce >= range.end {
+            range.next.store(range.end, Ordering::Relaxed);
+            return JobStatus::Finished(None);
+        }
+        backend.hash_batch(&job.midstate, nonce, &mut batch);
+        if batches % check_every == 0 {
+            if batches > 0 {
+                job.hashes.fetch_add(check_every * lanes, Ordering::Relaxed);
+            }
+            range.next.store(nonce, Ordering::Relaxed);
+            if cancelled() {
+                return JobStatus::Finished(None);
+            }
+            if let Some(max_cpu) = max_cpu {
+                throttle(&mut slice_started, max_cpu);
+            }
+            if paused.load(Ordering::Relaxed) {
+                while paused.load(Ordering::Relaxed) {
+                    if cancelled() {
+                        return JobStatus::Finished(None);
+                    }
+                    std::thread::sleep(PAUSE_POLL_INTERVAL);
+                }
+                slice_started = Instant::now();
+            }
+            if started.elapsed() >= TIME_SLICE {
+                return JobStatus::Yielded;
+            }
+        }
+        for (lane, next_hash) in batch.iter().enumerate() {
+            let found = nonce + lane as u64;
+            if next_hash.le(&job.difficulty) && found < range.end {
+                // The checkpoint keeps this solution until it is submitted, so searching
+                // the same ranges again picks up right after it
+                range.next.store(found + 1, Ordering::Relaxed);
+                job.found_solution.store(true, Ordering::Relaxed);
+                return JobStatus::Finished(Some((*next_hash, found)));
+            }
+        }
+        nonce = nonce.saturating_add(lanes);
+        batches += 1;
+    }
+}
+
+/// Sleeps long enough that the work slice since `slice_started` makes up only
+/// `max_cpu` percent of the time, then starts a new slice.
+fn throttle(slice_started: &mut Instant, max_cpu: u8) {
+    if max_cpu < 100 {
+        let busy = slice_started.elapsed();
+        let max_cpu = max_cpu.max(1) as u32;
+        std::thread::sleep(busy * (100 - max_cpu) / max_cpu);
+    }
+    *slice_started = Instant::now();
+}
+
+/// Lowers the scheduling priority of the calling thread.
+#[cfg(unix)]
+fn set_nice(nice: i32, events: bool) {
+    // On Linux, `setpriority` with a zero id applies to the calling thread only.
+    let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) };
+    if result != 0 {
+        log(
+            events,
+            format!(
+                "Failed to set worker niceness: {}",
+                std::io::Error::last_os_error()
+            ),
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn set_nice(_nice: i32, events: bool) {
+    log(events, "--nice is only supported on Unix");
+}